@@ -0,0 +1,29 @@
+#version 330 core
+
+uniform vec3 iResolution;
+uniform sampler2D iChannel0;
+
+// how bright a color has to be to glow
+uniform float threshold = 0.7;
+uniform float intensity = 0.6;
+// the size of the glow in pixels
+uniform float radius = 24.0;
+
+out vec4 fragColor;
+
+void main() {
+	vec2 uv = gl_FragCoord.xy / iResolution.xy;
+	vec4 color = texture(iChannel0, uv);
+	vec3 glow = vec3(0.0);
+	float totalWeight = 0.0;
+	for (int x = -4; x <= 4; x++) {
+		for (int y = -4; y <= 4; y++) {
+			vec2 offset = vec2(x, y) * radius / 4.0 / iResolution.xy;
+			float weight = exp(-float(x * x + y * y) / 8.0);
+			vec3 neighbor = texture(iChannel0, uv + offset).rgb;
+			glow += max(neighbor - threshold, 0.0) * weight;
+			totalWeight += weight;
+		}
+	}
+	fragColor = vec4(color.rgb + glow / totalWeight * intensity, color.a);
+}
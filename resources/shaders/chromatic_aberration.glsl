@@ -0,0 +1,19 @@
+#version 330 core
+
+uniform vec3 iResolution;
+uniform sampler2D iChannel0;
+
+// how far the red and blue channels are pulled apart at the edges,
+// as a fraction of the screen size
+uniform float amount = 0.004;
+
+out vec4 fragColor;
+
+void main() {
+	vec2 uv = gl_FragCoord.xy / iResolution.xy;
+	vec2 offset = (uv - 0.5) * amount;
+	vec4 color = texture(iChannel0, uv);
+	float red = texture(iChannel0, uv + offset).r;
+	float blue = texture(iChannel0, uv - offset).b;
+	fragColor = vec4(red, color.g, blue, color.a);
+}
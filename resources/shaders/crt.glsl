@@ -0,0 +1,24 @@
+#version 330 core
+
+uniform vec3 iResolution;
+uniform sampler2D iChannel0;
+
+// how much the screen bulges outwards
+uniform float curvature = 0.06;
+uniform float scanline_intensity = 0.2;
+uniform float scanline_count = 540.0;
+
+out vec4 fragColor;
+
+void main() {
+	vec2 centered = gl_FragCoord.xy / iResolution.xy * 2.0 - 1.0;
+	centered += centered * centered.yx * centered.yx * curvature;
+	vec2 uv = centered * 0.5 + 0.5;
+	if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+		fragColor = vec4(0.0, 0.0, 0.0, 1.0);
+		return;
+	}
+	vec4 color = texture(iChannel0, uv);
+	float scanline = 0.5 + 0.5 * cos(uv.y * scanline_count * 6.2831853);
+	fragColor = vec4(color.rgb * (1.0 - scanline_intensity * scanline), color.a);
+}
@@ -0,0 +1,23 @@
+#version 330 core
+
+uniform vec3 iResolution;
+uniform float iTime;
+uniform sampler2D iChannel0;
+
+uniform float amount = 0.05;
+
+out vec4 fragColor;
+
+float hash(vec3 p) {
+	p = fract(p * 0.1031);
+	p += dot(p, p.zyx + 31.32);
+	return fract((p.x + p.y) * p.z);
+}
+
+void main() {
+	vec2 uv = gl_FragCoord.xy / iResolution.xy;
+	vec4 color = texture(iChannel0, uv);
+	// the grain changes 60 times per second no matter the frame rate
+	float noise = hash(vec3(gl_FragCoord.xy, floor(iTime * 60.0))) - 0.5;
+	fragColor = vec4(color.rgb + noise * amount, color.a);
+}
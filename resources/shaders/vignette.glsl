@@ -0,0 +1,18 @@
+#version 330 core
+
+uniform vec3 iResolution;
+uniform sampler2D iChannel0;
+
+uniform float intensity = 0.4;
+// how gradually the edges darken (from 0 to 1)
+uniform float softness = 0.6;
+
+out vec4 fragColor;
+
+void main() {
+	vec2 uv = gl_FragCoord.xy / iResolution.xy;
+	vec4 color = texture(iChannel0, uv);
+	float distanceFromCenter = distance(uv, vec2(0.5)) * 1.4142136;
+	float shade = smoothstep(1.0 - softness, 1.0, distanceFromCenter);
+	fragColor = vec4(color.rgb * (1.0 - shade * intensity), color.a);
+}
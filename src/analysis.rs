@@ -0,0 +1,202 @@
+pub mod beat_tracking;
+pub mod chord_recognition;
+pub mod chroma;
+pub mod key_detection;
+pub mod midi_chords;
+
+use std::time::Duration;
+
+use kira::sound::static_sound::StaticSoundData;
+use rustfft::{num_complex::Complex, FftPlanner};
+
+use crate::{
+	interchange::TrackInfoBuilder,
+	track_info::TrackInfo,
+	user_track_info::{Change, UserTrackInfo},
+};
+
+/// A beat in the tempo map of a song's track info.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GridBeat {
+	timestamp: Duration,
+	/// Whether the beat starts a bar. Always `true` when there's no
+	/// time signature.
+	downbeat: bool,
+}
+
+/// Returns every beat in a song according to its track info.
+fn beat_grid(track_info: &TrackInfo, song_duration: Duration) -> Vec<GridBeat> {
+	let mut beats = vec![];
+	for (i, state) in track_info.music_states.iter().enumerate() {
+		let end = track_info
+			.music_states
+			.get(i + 1)
+			.map_or(song_duration, |next_state| next_state.timestamp);
+		let beat_duration = 60.0 / state.music_state.bpm;
+		if !beat_duration.is_finite() || beat_duration <= 0.0 {
+			continue;
+		}
+		for beat in 0.. {
+			let timestamp = state.timestamp + Duration::from_secs_f64(beat as f64 * beat_duration);
+			if timestamp >= end {
+				break;
+			}
+			beats.push(GridBeat {
+				timestamp,
+				downbeat: state
+					.music_state
+					.time_signature
+					.is_none_or(|time_signature| beat % time_signature.top.max(1) == 0),
+			});
+		}
+	}
+	beats
+}
+
+/// Returns the change to each music state in track info at the time
+/// it starts, with every field set.
+fn state_changes(track_info: &TrackInfo) -> Vec<(Duration, Change)> {
+	track_info
+		.music_states
+		.iter()
+		.map(|state| {
+			let music_state = &state.music_state;
+			let change = Change {
+				after: 0,
+				bpm: Some(music_state.bpm),
+				bpm_hidden: Some(music_state.bpm_hidden),
+				time_signature: Some(music_state.time_signature),
+				key: Some(music_state.key.clone()),
+				chord: Some(music_state.chord.clone()),
+				section: Some(music_state.section.clone()),
+				image_visibility: music_state.image_visibility.clone(),
+			};
+			(state.timestamp, change)
+		})
+		.collect()
+}
+
+/// Builds track info from changes at absolute timestamps (the `after`
+/// field of each change is ignored). Fields that are set to the value
+/// they already have don't create a change.
+fn build_track_info(
+	ticks_per_beat: u32,
+	mut changes: Vec<(Duration, Change)>,
+) -> anyhow::Result<UserTrackInfo> {
+	changes.sort_by_key(|(timestamp, _)| *timestamp);
+	let mut builder = TrackInfoBuilder::new(ticks_per_beat);
+	for (timestamp, change) in changes {
+		builder.advance_to(timestamp)?;
+		let state = builder.state();
+		let pending = builder.change();
+		if let Some(bpm) = change.bpm.filter(|bpm| *bpm != state.bpm) {
+			pending.bpm = Some(bpm);
+		}
+		if let Some(bpm_hidden) = change
+			.bpm_hidden
+			.filter(|hidden| *hidden != state.bpm_hidden)
+		{
+			pending.bpm_hidden = Some(bpm_hidden);
+		}
+		if let Some(time_signature) = change
+			.time_signature
+			.filter(|time_signature| *time_signature != state.time_signature)
+		{
+			pending.time_signature = Some(time_signature);
+		}
+		if let Some(key) = change.key.filter(|key| *key != state.key) {
+			pending.key = Some(key);
+		}
+		if let Some(chord) = change.chord.filter(|chord| *chord != state.chord) {
+			pending.chord = Some(chord);
+		}
+		if let Some(section) = change.section.filter(|section| *section != state.section) {
+			pending.section = Some(section);
+		}
+		for (id, visible) in change.image_visibility {
+			if state.image_visibility.get(&id) != Some(&visible) {
+				pending.image_visibility.insert(id, visible);
+			}
+		}
+	}
+	Ok(builder.build())
+}
+
+/// Picks one option for each step, maximizing the total score of the
+/// chosen options minus a penalty each time the choice changes (using
+/// the Viterbi algorithm). Returns the index of the option chosen for
+/// each step.
+fn best_path(step_scores: &[Vec<f64>], change_penalty: f64) -> Vec<usize> {
+	let Some(first_scores) = step_scores.first() else {
+		return vec![];
+	};
+	let num_options = first_scores.len();
+	let mut totals = first_scores.clone();
+	let mut previous_options = vec![vec![0; num_options]];
+	for scores in &step_scores[1..] {
+		let (best_option, best_total) = totals
+			.iter()
+			.copied()
+			.enumerate()
+			.max_by(|(_, a), (_, b)| a.total_cmp(b))
+			.unwrap();
+		let mut step_previous_options = vec![0; num_options];
+		let mut new_totals = vec![0.0; num_options];
+		for option in 0..num_options {
+			let (previous_option, total) = if totals[option] >= best_total - change_penalty {
+				(option, totals[option])
+			} else {
+				(best_option, best_total - change_penalty)
+			};
+			step_previous_options[option] = previous_option;
+			new_totals[option] = total + scores[option];
+		}
+		totals = new_totals;
+		previous_options.push(step_previous_options);
+	}
+	let mut option = totals
+		.iter()
+		.copied()
+		.enumerate()
+		.max_by(|(_, a), (_, b)| a.total_cmp(b))
+		.unwrap()
+		.0;
+	let mut path = vec![0; step_scores.len()];
+	for step in (0..step_scores.len()).rev() {
+		path[step] = option;
+		option = previous_options[step][option];
+	}
+	path
+}
+
+/// Calls `f` with the magnitude spectrum of each hop through a song
+/// (mixed down to mono), along with the index of the hop. Each window
+/// starts at `index * hop_size` frames into the song.
+fn for_each_spectrum(
+	sound_data: &StaticSoundData,
+	window_size: usize,
+	hop_size: usize,
+	mut f: impl FnMut(usize, &[f32]),
+) {
+	let fft = FftPlanner::new().plan_fft_forward(window_size);
+	let window = (0..window_size)
+		.map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / window_size as f32).cos())
+		.collect::<Vec<_>>();
+	let mut buffer = vec![Complex::default(); window_size];
+	let mut magnitudes = vec![0.0; window_size / 2];
+	let frames = &sound_data.frames;
+	for (index, start) in (0..frames.len()).step_by(hop_size).enumerate() {
+		for (i, (sample, window)) in buffer.iter_mut().zip(&window).enumerate() {
+			let value = frames
+				.get(start + i)
+				.map(|frame| (frame.left + frame.right) / 2.0)
+				.unwrap_or(0.0);
+			*sample = Complex::new(value * window, 0.0);
+		}
+		fft.process(&mut buffer);
+		for (magnitude, bin) in magnitudes.iter_mut().zip(&buffer) {
+			*magnitude = bin.norm();
+		}
+		f(index, &magnitudes);
+	}
+}
@@ -0,0 +1,421 @@
+use std::time::Duration;
+
+use anyhow::bail;
+use kira::sound::static_sound::StaticSoundData;
+
+use crate::{
+	music_theory::TimeSignature,
+	track_info::TrackInfo,
+	user_track_info::{Change, UserTrackInfo},
+};
+
+use super::{build_track_info, for_each_spectrum, state_changes};
+
+const WINDOW_SIZE: usize = 2048;
+const HOP_SIZE: usize = 512;
+/// Onsets below this frequency (in Hz) are used to find downbeats,
+/// since kick drums and bass notes tend to land on them.
+const LOW_FREQUENCY_CUTOFF: f32 = 200.0;
+/// How strongly the beat tracker prefers beats that are the estimated
+/// tempo apart over beats that line up with onsets.
+const TIGHTNESS: f64 = 100.0;
+/// Ticks per beat of generated tempo maps. High enough that rounding
+/// beats to the nearest tick isn't noticeable.
+const TICKS_PER_BEAT: u32 = 960;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BeatTrackingSettings {
+	pub min_bpm: f64,
+	pub max_bpm: f64,
+	/// The number of beats in a bar, or `None` to guess between 3 and 4.
+	pub beats_per_bar: Option<u32>,
+	/// How far (in beats) the tempo map can drift away from the detected
+	/// beats before a tempo change is added.
+	pub drift_tolerance: f64,
+	/// Whether to keep the chord, key, section and image visibility
+	/// changes from the current track info.
+	pub keep_chords_and_keys: bool,
+}
+
+impl Default for BeatTrackingSettings {
+	fn default() -> Self {
+		Self {
+			min_bpm: 60.0,
+			max_bpm: 200.0,
+			beats_per_bar: None,
+			drift_tolerance: 0.05,
+			keep_chords_and_keys: true,
+		}
+	}
+}
+
+/// The beats found in a song by [`BeatTracking::analyze`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeatTracking {
+	/// The time of each beat in seconds.
+	pub beats: Vec<f64>,
+	pub beats_per_bar: u32,
+	/// The index of the first beat that starts a bar.
+	pub first_downbeat: usize,
+	pub report: BeatTrackingReport,
+}
+
+/// How the beat tracking went, so the user can tell whether the
+/// tempo map is worth using.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BeatTrackingReport {
+	pub average_bpm: f64,
+	/// The tempo of the slowest bar.
+	pub min_bpm: f64,
+	/// The tempo of the fastest bar.
+	pub max_bpm: f64,
+	/// How strongly the onsets in the song repeat at the detected
+	/// tempo (from 0 to 1).
+	pub tempo_confidence: f64,
+	/// The fraction of beats that line up with an onset.
+	pub beat_confidence: f64,
+	/// How much more the chosen downbeats stand out than the next best
+	/// choice (from 0 to 1).
+	pub downbeat_confidence: f64,
+}
+
+impl BeatTracking {
+	/// Finds the beats and downbeats in a song using onset detection
+	/// and dynamic programming beat tracking (as described by Ellis in
+	/// "Beat Tracking by Dynamic Programming").
+	pub fn analyze(
+		sound_data: &StaticSoundData,
+		settings: BeatTrackingSettings,
+	) -> anyhow::Result<Self> {
+		let frame_rate = sound_data.sample_rate as f64 / HOP_SIZE as f64;
+		let (onsets, low_onsets) = onset_envelopes(sound_data);
+		let Some((period, tempo_confidence)) = estimate_period(&onsets, frame_rate, settings)
+		else {
+			bail!("the song is too short to find the tempo");
+		};
+		let beat_frames = track_beats(&onsets, period);
+		if beat_frames.len() < 2 {
+			bail!("couldn't find any beats");
+		}
+		let beat_confidence = beat_frames
+			.iter()
+			.filter(|frame| strength_at(&onsets, **frame) > 0.5)
+			.count() as f64
+			/ beat_frames.len() as f64;
+		let (beats_per_bar, first_downbeat, downbeat_confidence) =
+			find_downbeats(&low_onsets, &beat_frames, settings.beats_per_bar);
+		let beats = beat_frames
+			.iter()
+			.map(|frame| {
+				(frame * HOP_SIZE + WINDOW_SIZE / 2) as f64 / sound_data.sample_rate as f64
+			})
+			.collect::<Vec<_>>();
+		let mut tracking = Self {
+			report: BeatTrackingReport {
+				average_bpm: 60.0 * (beats.len() - 1) as f64 / (beats[beats.len() - 1] - beats[0]),
+				min_bpm: 0.0,
+				max_bpm: 0.0,
+				tempo_confidence,
+				beat_confidence,
+				downbeat_confidence,
+			},
+			beats,
+			beats_per_bar,
+			first_downbeat,
+		};
+		let bar_bpms = tracking.bars().map(|(_, _, bpm)| bpm).collect::<Vec<_>>();
+		tracking.report.min_bpm = bar_bpms
+			.iter()
+			.copied()
+			.fold(tracking.report.average_bpm, f64::min);
+		tracking.report.max_bpm = bar_bpms
+			.iter()
+			.copied()
+			.fold(tracking.report.average_bpm, f64::max);
+		Ok(tracking)
+	}
+
+	/// Creates track info with a tempo change wherever the detected
+	/// beats drift too far from the current tempo. Tempo changes are
+	/// always on downbeats, and the time signature starts at the first
+	/// downbeat.
+	pub fn tempo_map(
+		&self,
+		track_info: &TrackInfo,
+		settings: BeatTrackingSettings,
+	) -> anyhow::Result<UserTrackInfo> {
+		let mut changes = vec![];
+		// (the time of the last tempo change, the beat it happened on,
+		// and the tempo)
+		let mut grid: Option<(f64, usize, f64)> = None;
+		for (start_beat, end_beat, bpm) in self.bars() {
+			let bpm = (bpm * 10.0).round() / 10.0;
+			let drifted = match grid {
+				None => true,
+				Some((grid_start, grid_beat, grid_bpm)) => {
+					let expected_end = grid_start + (end_beat - grid_beat) as f64 * 60.0 / grid_bpm;
+					(self.beats[end_beat] - expected_end).abs() * grid_bpm / 60.0
+						> settings.drift_tolerance
+				}
+			};
+			if !drifted {
+				continue;
+			}
+			let start = self.beats[start_beat];
+			let mut change = Change {
+				bpm: Some(bpm),
+				..Default::default()
+			};
+			if grid.is_none() {
+				change.time_signature = Some(Some(TimeSignature {
+					top: self.beats_per_bar,
+					bottom: 4,
+				}));
+				// use the first tempo for any pickup before the first
+				// downbeat, too
+				changes.push((
+					Duration::ZERO,
+					Change {
+						bpm: Some(bpm),
+						..Default::default()
+					},
+				));
+			}
+			changes.push((Duration::from_secs_f64(start), change));
+			grid = Some((start, start_beat, bpm));
+		}
+		if settings.keep_chords_and_keys {
+			changes.extend(
+				state_changes(track_info)
+					.into_iter()
+					.map(|(timestamp, change)| {
+						let change = Change {
+							bpm: None,
+							time_signature: None,
+							..change
+						};
+						(timestamp, change)
+					}),
+			);
+		}
+		build_track_info(TICKS_PER_BEAT, changes)
+	}
+
+	/// Returns the index of the first and last beat of each bar (the
+	/// last one being the downbeat of the next bar) and the bar's tempo.
+	fn bars(&self) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+		(self.first_downbeat..self.beats.len() - 1)
+			.step_by(self.beats_per_bar as usize)
+			.map(|start| {
+				let end = (start + self.beats_per_bar as usize).min(self.beats.len() - 1);
+				let bpm = 60.0 * (end - start) as f64 / (self.beats[end] - self.beats[start]);
+				(start, end, bpm)
+			})
+	}
+}
+
+/// Returns how much the spectrum of the song increases at each hop,
+/// both over all frequencies and for just the low frequencies.
+/// Both are normalized to have a mean of 0 and a standard deviation
+/// of 1.
+fn onset_envelopes(sound_data: &StaticSoundData) -> (Vec<f64>, Vec<f64>) {
+	let bin_frequency = sound_data.sample_rate as f32 / WINDOW_SIZE as f32;
+	let low_bins = (LOW_FREQUENCY_CUTOFF / bin_frequency).ceil() as usize;
+	let mut previous = vec![0.0f32; WINDOW_SIZE / 2];
+	let mut onsets = vec![];
+	let mut low_onsets = vec![];
+	for_each_spectrum(sound_data, WINDOW_SIZE, HOP_SIZE, |_, magnitudes| {
+		let mut onset = 0.0;
+		let mut low_onset = 0.0;
+		for (bin, (magnitude, previous)) in magnitudes.iter().zip(&mut previous).enumerate() {
+			// log compression makes quiet onsets count for more
+			let compressed = (100.0 * magnitude).ln_1p();
+			let increase = (compressed - *previous).max(0.0) as f64;
+			onset += increase;
+			if bin < low_bins {
+				low_onset += increase;
+			}
+			*previous = compressed;
+		}
+		onsets.push(onset);
+		low_onsets.push(low_onset);
+	});
+	(normalize(onsets), normalize(low_onsets))
+}
+
+fn normalize(mut values: Vec<f64>) -> Vec<f64> {
+	let mean = values.iter().sum::<f64>() / values.len().max(1) as f64;
+	let variance = values
+		.iter()
+		.map(|value| (value - mean).powi(2))
+		.sum::<f64>()
+		/ values.len().max(1) as f64;
+	let standard_deviation = variance.sqrt().max(f64::EPSILON);
+	for value in &mut values {
+		*value = (*value - mean) / standard_deviation;
+	}
+	values
+}
+
+/// Estimates the number of hops between beats from the autocorrelation
+/// of the onsets, preferring tempos near 120 BPM. Also returns the
+/// normalized autocorrelation at that period.
+fn estimate_period(
+	onsets: &[f64],
+	frame_rate: f64,
+	settings: BeatTrackingSettings,
+) -> Option<(f64, f64)> {
+	let min_lag = ((frame_rate * 60.0 / settings.max_bpm).floor() as usize).max(1);
+	let max_lag =
+		((frame_rate * 60.0 / settings.min_bpm).ceil() as usize).min(onsets.len().checked_sub(1)?);
+	if min_lag > max_lag {
+		return None;
+	}
+	let energy = onsets.iter().map(|onset| onset * onset).sum::<f64>() / onsets.len() as f64;
+	let autocorrelation = |lag: usize| {
+		onsets
+			.iter()
+			.zip(&onsets[lag..])
+			.map(|(a, b)| a * b)
+			.sum::<f64>()
+			/ (onsets.len() - lag) as f64
+			/ energy.max(f64::EPSILON)
+	};
+	let values = (min_lag..=max_lag)
+		.map(|lag| (lag, autocorrelation(lag)))
+		.collect::<Vec<_>>();
+	let (best_index, &(best_lag, best_value)) =
+		values
+			.iter()
+			.enumerate()
+			.max_by(|(_, (lag_a, a)), (_, (lag_b, b))| {
+				let weight = |lag: usize| {
+					let bpm = frame_rate * 60.0 / lag as f64;
+					(-0.5 * (bpm / 120.0).log2().powi(2)).exp()
+				};
+				(a * weight(*lag_a)).total_cmp(&(b * weight(*lag_b)))
+			})?;
+	// fit a parabola through the neighboring lags to get a period
+	// between whole hops
+	let mut period = best_lag as f64;
+	if let (Some(&(_, before)), Some(&(_, after))) = (
+		best_index
+			.checked_sub(1)
+			.and_then(|index| values.get(index)),
+		values.get(best_index + 1),
+	) {
+		let curvature = before - 2.0 * best_value + after;
+		if curvature < 0.0 {
+			period += (0.5 * (before - after) / curvature).clamp(-0.5, 0.5);
+		}
+	}
+	Some((period, best_value.clamp(0.0, 1.0)))
+}
+
+/// Finds the sequence of hops that best lines up with the onsets while
+/// staying close to the given period.
+fn track_beats(onsets: &[f64], period: f64) -> Vec<usize> {
+	let min_gap = ((period / 2.0).round() as usize).max(1);
+	let max_gap = ((period * 2.0).round() as usize).max(min_gap);
+	let mut scores = vec![0.0; onsets.len()];
+	let mut previous_beats = vec![None; onsets.len()];
+	for frame in 0..onsets.len() {
+		let best_previous = (frame.saturating_sub(max_gap)..=frame.saturating_sub(min_gap))
+			.filter(|previous| frame - previous >= min_gap)
+			.map(|previous| {
+				let gap = (frame - previous) as f64;
+				let score = scores[previous] - TIGHTNESS * (gap / period).ln().powi(2);
+				(previous, score)
+			})
+			.max_by(|(_, a), (_, b)| a.total_cmp(b));
+		scores[frame] = onsets[frame];
+		// start a new sequence of beats if continuing an old one
+		// doesn't help (like after a silent intro)
+		if let Some((previous, score)) = best_previous.filter(|(_, score)| *score > 0.0) {
+			scores[frame] += score;
+			previous_beats[frame] = Some(previous);
+		}
+	}
+	let search_start = onsets.len().saturating_sub(period.ceil() as usize);
+	let Some(last_beat) =
+		(search_start..onsets.len()).max_by(|a, b| scores[*a].total_cmp(&scores[*b]))
+	else {
+		return vec![];
+	};
+	let mut beats = vec![last_beat];
+	while let Some(previous) = previous_beats[*beats.last().unwrap()] {
+		beats.push(previous);
+	}
+	beats.reverse();
+	// beats in quiet parts at the start and end of the song are just
+	// the beat tracker keeping time
+	while beats.len() > 2 && strength_at(onsets, beats[0]) < 0.0 {
+		beats.remove(0);
+	}
+	while beats.len() > 2 && strength_at(onsets, beats[beats.len() - 1]) < 0.0 {
+		beats.pop();
+	}
+	beats
+}
+
+/// Returns the strongest onset within a couple hops of a frame, since
+/// beats don't always land exactly on the peak.
+fn strength_at(onsets: &[f64], frame: usize) -> f64 {
+	onsets[frame.saturating_sub(2)..(frame + 3).min(onsets.len())]
+		.iter()
+		.copied()
+		.fold(f64::NEG_INFINITY, f64::max)
+}
+
+/// Picks the number of beats per bar and the first downbeat by finding
+/// the beats with the strongest low frequency onsets. Also returns how
+/// much better that choice is than the next best one.
+fn find_downbeats(
+	low_onsets: &[f64],
+	beat_frames: &[usize],
+	beats_per_bar: Option<u32>,
+) -> (u32, usize, f64) {
+	let strengths = beat_frames
+		.iter()
+		.map(|frame| strength_at(low_onsets, *frame))
+		.collect::<Vec<_>>();
+	let candidates = beats_per_bar.map_or(vec![3, 4], |beats_per_bar| vec![beats_per_bar.max(1)]);
+	let mut best: Option<(u32, usize, f64)> = None;
+	let mut confidence = 1.0;
+	for beats_per_bar in candidates {
+		let mut scores = (0..beats_per_bar as usize)
+			.map(|phase| {
+				let (on, off): (Vec<_>, Vec<_>) = strengths
+					.iter()
+					.enumerate()
+					.partition(|(i, _)| i % beats_per_bar as usize == phase);
+				let mean = |strengths: Vec<(usize, &f64)>| {
+					strengths
+						.iter()
+						.map(|(_, strength)| **strength)
+						.sum::<f64>() / strengths.len().max(1) as f64
+				};
+				let score = if off.is_empty() {
+					0.0
+				} else {
+					mean(on) - mean(off)
+				};
+				(phase, score)
+			})
+			.collect::<Vec<_>>();
+		scores.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+		let (phase, score) = scores[0];
+		if best.is_none_or(|(_, _, best_score)| score > best_score) {
+			best = Some((beats_per_bar, phase, score));
+			confidence = match scores.get(1) {
+				Some((_, second_score)) if score > 0.0 => {
+					((score - second_score) / score).clamp(0.0, 1.0)
+				}
+				Some(_) => 0.0,
+				None => 1.0,
+			};
+		}
+	}
+	let (beats_per_bar, phase, _) = best.unwrap();
+	(beats_per_bar, phase, confidence)
+}
@@ -0,0 +1,254 @@
+use std::time::Duration;
+
+use crate::{
+	music_theory::{pitch_class_name, spell_pitch_class, CHORD_QUALITIES},
+	track_info::TrackInfo,
+	user_track_info::{Change, UserTrackInfo},
+};
+
+use super::{beat_grid, best_path, build_track_info, chroma::Chromagram, state_changes};
+
+/// The number of qualities at the start of [`CHORD_QUALITIES`] that
+/// are recognized when extended chords are turned on. Rarer chords are
+/// too easy to mistake for each other in audio.
+const NUM_EXTENDED_QUALITIES: usize = 7;
+/// The number of qualities at the start of [`CHORD_QUALITIES`] that
+/// are always recognized.
+const NUM_BASIC_QUALITIES: usize = 2;
+/// How much the fit of qualities other than major and minor is scaled
+/// by, so they're only picked when they fit clearly better.
+const EXTENDED_QUALITY_WEIGHT: f64 = 0.95;
+/// Steps quieter than this fraction of the median step are treated as
+/// having no chord.
+const SILENCE_THRESHOLD: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChordRecognitionSettings {
+	/// How much better another chord has to fit before the chord
+	/// changes. Higher values give fewer chord changes.
+	pub change_penalty: f64,
+	/// Whether chords can only change at the start of a bar instead of
+	/// on any beat.
+	pub downbeats_only: bool,
+	/// Whether to recognize seventh, diminished and suspended chords as
+	/// well as major and minor ones.
+	pub extended_chords: bool,
+}
+
+impl Default for ChordRecognitionSettings {
+	fn default() -> Self {
+		Self {
+			change_penalty: 0.1,
+			downbeats_only: false,
+			extended_chords: true,
+		}
+	}
+}
+
+/// A chord lasting for one or more beats.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecognizedChord {
+	pub start: Duration,
+	/// The chord name, or `None` if nothing is playing.
+	pub chord: Option<String>,
+	/// How well the audio fits the chord (from 0 to 1).
+	pub fit: f64,
+}
+
+/// A stretch of the song where the current and proposed chords both
+/// stay the same.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChordDiff {
+	pub start: Duration,
+	pub current: Option<String>,
+	pub proposed: Option<String>,
+}
+
+/// The chords found in a song by [`ChordRecognition::analyze`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChordRecognition {
+	pub chords: Vec<RecognizedChord>,
+}
+
+impl ChordRecognition {
+	/// Estimates the chord on each beat (or bar) of the track info's
+	/// beat grid by comparing the pitch classes playing to chord
+	/// templates, then picks the sequence of chords that fits best with
+	/// a penalty for each chord change. Chord roots are spelled as scale
+	/// degrees of the key at that point in the song.
+	pub fn analyze(
+		chromagram: &Chromagram,
+		track_info: &TrackInfo,
+		song_duration: Duration,
+		settings: ChordRecognitionSettings,
+	) -> Self {
+		let step_starts = beat_grid(track_info, song_duration)
+			.into_iter()
+			.filter(|beat| beat.downbeat || !settings.downbeats_only)
+			.map(|beat| beat.timestamp)
+			.collect::<Vec<_>>();
+		let step_chromas = step_starts
+			.iter()
+			.enumerate()
+			.map(|(i, start)| {
+				let end = step_starts.get(i + 1).copied().unwrap_or(song_duration);
+				let chroma = chromagram.sum(start.as_secs_f64(), end.as_secs_f64());
+				let loudness = chroma.iter().sum::<f32>() / (end - *start).as_secs_f32().max(0.001);
+				(chroma, loudness)
+			})
+			.collect::<Vec<_>>();
+		let mut loudnesses = step_chromas
+			.iter()
+			.map(|(_, loudness)| *loudness)
+			.collect::<Vec<_>>();
+		loudnesses.sort_by(f32::total_cmp);
+		let median_loudness = loudnesses.get(loudnesses.len() / 2).copied().unwrap_or(0.0);
+		let num_qualities = if settings.extended_chords {
+			NUM_EXTENDED_QUALITIES
+		} else {
+			NUM_BASIC_QUALITIES
+		};
+		// the options for each step are every root and quality, followed
+		// by no chord
+		let step_scores = step_chromas
+			.iter()
+			.map(|(chroma, loudness)| {
+				let silent = *loudness <= median_loudness * SILENCE_THRESHOLD;
+				let mut scores = (0..12)
+					.flat_map(|root| {
+						CHORD_QUALITIES[..num_qualities].iter().enumerate().map(
+							move |(quality, (_, intervals))| {
+								let weight = if quality < NUM_BASIC_QUALITIES {
+									1.0
+								} else {
+									EXTENDED_QUALITY_WEIGHT
+								};
+								if silent {
+									0.0
+								} else {
+									template_fit(chroma, root, intervals) * weight
+								}
+							},
+						)
+					})
+					.collect::<Vec<_>>();
+				scores.push(if silent { 1.0 } else { 0.0 });
+				scores
+			})
+			.collect::<Vec<_>>();
+		let path = best_path(&step_scores, settings.change_penalty);
+		let mut chords: Vec<RecognizedChord> = vec![];
+		let mut fits: Vec<f64> = vec![];
+		for (step, option) in path.iter().enumerate() {
+			if step > 0 && path[step - 1] == *option {
+				fits.push(step_scores[step][*option]);
+				continue;
+			}
+			if let Some(chord) = chords.last_mut() {
+				chord.fit = fits.iter().sum::<f64>() / fits.len() as f64;
+			}
+			fits = vec![step_scores[step][*option]];
+			let start = step_starts[step];
+			let chord = (*option < 12 * num_qualities).then(|| {
+				let root = option / num_qualities;
+				let (suffix, _) = CHORD_QUALITIES[option % num_qualities];
+				let root_name = match &track_info.music_state(start).music_state.key {
+					Some(key) => spell_pitch_class(root, key),
+					None => pitch_class_name(root, false).to_string(),
+				};
+				format!("{}{}", root_name, suffix)
+			});
+			chords.push(RecognizedChord {
+				start,
+				chord,
+				fit: 0.0,
+			});
+		}
+		if let Some(chord) = chords.last_mut() {
+			chord.fit = fits.iter().sum::<f64>() / fits.len().max(1) as f64;
+		}
+		Self { chords }
+	}
+
+	/// Compares the recognized chords to the chords in the track info.
+	pub fn diff(&self, track_info: &TrackInfo) -> Vec<ChordDiff> {
+		let mut change_times = self
+			.chords
+			.iter()
+			.map(|chord| chord.start)
+			.chain(track_info.music_states.iter().map(|state| state.timestamp))
+			.collect::<Vec<_>>();
+		change_times.sort();
+		change_times.dedup();
+		let mut diffs: Vec<ChordDiff> = vec![];
+		for start in change_times {
+			let current = track_info.music_state(start).music_state.chord.clone();
+			let proposed = self
+				.chords
+				.iter()
+				.rev()
+				.find(|chord| chord.start <= start)
+				.and_then(|chord| chord.chord.clone());
+			if let Some(previous) = diffs.last() {
+				if previous.current == current && previous.proposed == proposed {
+					continue;
+				}
+			}
+			diffs.push(ChordDiff {
+				start,
+				current,
+				proposed,
+			});
+		}
+		diffs
+	}
+
+	/// Returns the track info with its chords replaced by the
+	/// recognized ones.
+	pub fn suggested_track_info(
+		&self,
+		user_track_info: &UserTrackInfo,
+	) -> anyhow::Result<UserTrackInfo> {
+		let track_info = TrackInfo::new(user_track_info);
+		let mut changes = state_changes(&track_info)
+			.into_iter()
+			.map(|(timestamp, change)| {
+				(
+					timestamp,
+					Change {
+						chord: None,
+						..change
+					},
+				)
+			})
+			.collect::<Vec<_>>();
+		for chord in &self.chords {
+			changes.push((
+				chord.start,
+				Change {
+					chord: Some(chord.chord.clone()),
+					..Default::default()
+				},
+			));
+		}
+		build_track_info(user_track_info.ticks_per_beat, changes)
+	}
+}
+
+/// Returns the cosine similarity between the pitch classes playing and
+/// a chord's notes.
+fn template_fit(chroma: &[f32; 12], root: usize, intervals: &[usize]) -> f64 {
+	let length = chroma
+		.iter()
+		.map(|value| (*value as f64).powi(2))
+		.sum::<f64>()
+		.sqrt();
+	if length == 0.0 {
+		return 0.0;
+	}
+	let dot_product = intervals
+		.iter()
+		.map(|interval| chroma[(root + interval) % 12] as f64)
+		.sum::<f64>();
+	dot_product / (length * (intervals.len() as f64).sqrt())
+}
@@ -0,0 +1,76 @@
+use kira::sound::static_sound::StaticSoundData;
+
+use super::for_each_spectrum;
+
+const WINDOW_SIZE: usize = 8192;
+const HOP_SIZE: usize = 4096;
+/// The range of frequencies (in Hz) that count towards the chromagram.
+/// Lower notes are too close together for the FFT to tell apart, and
+/// higher frequencies are mostly overtones and noise.
+const MIN_FREQUENCY: f32 = 100.0;
+const MAX_FREQUENCY: f32 = 4000.0;
+
+/// How much of each pitch class (starting from C) is playing
+/// throughout a song.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chromagram {
+	pub frames: Vec<[f32; 12]>,
+	/// The time between frames in seconds.
+	pub frame_duration: f64,
+}
+
+impl Chromagram {
+	pub fn analyze(sound_data: &StaticSoundData) -> Self {
+		let bin_frequency = sound_data.sample_rate as f32 / WINDOW_SIZE as f32;
+		// the pitch class each FFT bin belongs to, and how close the bin
+		// is to the center of the semitone
+		let bins = (0..WINDOW_SIZE / 2)
+			.map(|bin| {
+				let frequency = bin as f32 * bin_frequency;
+				if !(MIN_FREQUENCY..=MAX_FREQUENCY).contains(&frequency) {
+					return None;
+				}
+				let note = 69.0 + 12.0 * (frequency / 440.0).log2();
+				let pitch_class = (note.round() as i32).rem_euclid(12) as usize;
+				let weight = (std::f32::consts::PI * (note - note.round())).cos().powi(2);
+				Some((pitch_class, weight))
+			})
+			.collect::<Vec<_>>();
+		let mut frames = vec![];
+		for_each_spectrum(sound_data, WINDOW_SIZE, HOP_SIZE, |_, magnitudes| {
+			let mut chroma = [0.0; 12];
+			for (magnitude, bin) in magnitudes.iter().zip(&bins) {
+				if let Some((pitch_class, weight)) = bin {
+					chroma[*pitch_class] += magnitude * weight;
+				}
+			}
+			frames.push(chroma);
+		});
+		Self {
+			frames,
+			frame_duration: HOP_SIZE as f64 / sound_data.sample_rate as f64,
+		}
+	}
+
+	/// Returns the total of each pitch class between two times
+	/// (in seconds).
+	pub fn sum(&self, start: f64, end: f64) -> [f32; 12] {
+		let start_frame = self.frame_index(start);
+		let end_frame = self.frame_index(end).max(start_frame);
+		let mut sum = [0.0; 12];
+		for frame in &self.frames[start_frame..end_frame] {
+			for (total, value) in sum.iter_mut().zip(frame) {
+				*total += value;
+			}
+		}
+		sum
+	}
+
+	/// Returns the index of the frame centered closest to a time.
+	fn frame_index(&self, time: f64) -> usize {
+		// each window is two hops long, so frame `i` is centered
+		// on hop `i + 1`
+		let index = (time / self.frame_duration - 1.0).round().max(0.0) as usize;
+		index.min(self.frames.len())
+	}
+}
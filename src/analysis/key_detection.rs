@@ -0,0 +1,229 @@
+use std::time::Duration;
+
+use crate::{
+	music_theory::key_name,
+	track_info::TrackInfo,
+	user_track_info::{Change, UserTrackInfo},
+};
+
+use super::{beat_grid, best_path, build_track_info, chroma::Chromagram, state_changes};
+
+/// How much each pitch class (relative to the tonic) fits in a major
+/// key, from Krumhansl and Kessler's probe tone experiments.
+const MAJOR_PROFILE: [f64; 12] = [
+	6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+/// How much each pitch class (relative to the tonic) fits in a minor key.
+const MINOR_PROFILE: [f64; 12] = [
+	6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+/// The length (in seconds) of the steps the song is divided into when
+/// looking for key changes.
+const STEP_DURATION: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyDetectionSettings {
+	/// The length of audio (in seconds) each local key estimate
+	/// listens to.
+	pub window_duration: f64,
+	/// How much better another key has to fit the music before a key
+	/// change is suggested. Higher values suggest fewer key changes.
+	pub change_penalty: f64,
+}
+
+impl Default for KeyDetectionSettings {
+	fn default() -> Self {
+		Self {
+			window_duration: 12.0,
+			change_penalty: 4.0,
+		}
+	}
+}
+
+/// A key and how well the music fits it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyEstimate {
+	/// The pitch class of the tonic, where 0 is C.
+	pub tonic: usize,
+	pub minor: bool,
+	/// The correlation between the pitch classes in the music and the
+	/// key's profile (from -1 to 1).
+	pub correlation: f64,
+}
+
+impl KeyEstimate {
+	pub fn name(&self) -> &'static str {
+		key_name(self.tonic, self.minor)
+	}
+}
+
+/// A part of the song that seems to be in one key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeySegment {
+	/// The start of the segment in seconds.
+	pub start: f64,
+	/// The key, with the average correlation over the segment.
+	pub key: KeyEstimate,
+}
+
+/// The keys found in a song by [`KeyDetection::analyze`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyDetection {
+	/// The key that fits the whole song best.
+	pub global_key: KeyEstimate,
+	/// The key of each part of the song. Has more than one segment
+	/// if the song seems to modulate.
+	pub segments: Vec<KeySegment>,
+}
+
+impl KeyDetection {
+	/// Finds the key of a song by comparing its pitch classes to the
+	/// Krumhansl-Kessler key profiles. Key changes are found by comparing
+	/// windows of the song to the profiles and picking the sequence of
+	/// keys that fits best, with a penalty for each key change.
+	pub fn analyze(chromagram: &Chromagram, settings: KeyDetectionSettings) -> Self {
+		let duration = chromagram.frames.len() as f64 * chromagram.frame_duration;
+		let global_correlations = correlations(&chromagram.sum(0.0, duration));
+		let global_key = best_key(&global_correlations);
+		let num_steps = ((duration / STEP_DURATION).ceil() as usize).max(1);
+		let step_correlations = (0..num_steps)
+			.map(|step| {
+				let center = (step as f64 + 0.5) * STEP_DURATION;
+				correlations(&chromagram.sum(
+					center - settings.window_duration / 2.0,
+					center + settings.window_duration / 2.0,
+				))
+			})
+			.collect::<Vec<_>>();
+		let keys = best_path(
+			&step_correlations
+				.iter()
+				.map(|correlations| correlations.to_vec())
+				.collect::<Vec<_>>(),
+			settings.change_penalty,
+		);
+		let mut segments: Vec<KeySegment> = vec![];
+		let mut segment_start_step = 0;
+		for step in 0..num_steps {
+			let is_last_step = step + 1 == num_steps;
+			if !is_last_step && keys[step + 1] == keys[step] {
+				continue;
+			}
+			let key = keys[step];
+			let correlation = step_correlations[segment_start_step..=step]
+				.iter()
+				.map(|correlations| correlations[key])
+				.sum::<f64>()
+				/ (step + 1 - segment_start_step) as f64;
+			segments.push(KeySegment {
+				start: segment_start_step as f64 * STEP_DURATION,
+				key: KeyEstimate {
+					tonic: key % 12,
+					minor: key >= 12,
+					correlation,
+				},
+			});
+			segment_start_step = step + 1;
+		}
+		Self {
+			global_key,
+			segments,
+		}
+	}
+
+	/// Returns the track info with its keys replaced by the detected
+	/// ones. Key changes are moved to the nearest downbeat so they don't
+	/// throw off the beat count.
+	pub fn suggested_track_info(
+		&self,
+		user_track_info: &UserTrackInfo,
+		song_duration: Duration,
+	) -> anyhow::Result<UserTrackInfo> {
+		let track_info = TrackInfo::new(user_track_info);
+		let grid = beat_grid(&track_info, song_duration);
+		let mut changes = state_changes(&track_info)
+			.into_iter()
+			.map(|(timestamp, change)| {
+				(
+					timestamp,
+					Change {
+						key: None,
+						..change
+					},
+				)
+			})
+			.collect::<Vec<_>>();
+		for (i, segment) in self.segments.iter().enumerate() {
+			let timestamp = if i == 0 {
+				Duration::ZERO
+			} else {
+				let start = Duration::from_secs_f64(segment.start);
+				grid.iter()
+					.filter(|beat| beat.downbeat)
+					.map(|beat| beat.timestamp)
+					.min_by_key(|timestamp| timestamp.abs_diff(start))
+					.unwrap_or(start)
+			};
+			changes.push((
+				timestamp,
+				Change {
+					key: Some(Some(segment.key.name().to_string())),
+					..Default::default()
+				},
+			));
+		}
+		build_track_info(user_track_info.ticks_per_beat, changes)
+	}
+}
+
+/// Returns the correlation between the pitch classes and each key's
+/// profile. The first 12 keys are major and the last 12 are minor,
+/// each starting from C.
+fn correlations(chroma: &[f32; 12]) -> [f64; 24] {
+	let mut correlations = [0.0; 24];
+	for (key, correlation) in correlations.iter_mut().enumerate() {
+		let profile = if key < 12 {
+			&MAJOR_PROFILE
+		} else {
+			&MINOR_PROFILE
+		};
+		let tonic = key % 12;
+		let rotated = (0..12)
+			.map(|pitch_class| profile[(pitch_class + 12 - tonic) % 12])
+			.collect::<Vec<_>>();
+		*correlation = pearson_correlation(chroma.map(f64::from).as_slice(), &rotated);
+	}
+	correlations
+}
+
+fn pearson_correlation(a: &[f64], b: &[f64]) -> f64 {
+	let mean_a = a.iter().sum::<f64>() / a.len() as f64;
+	let mean_b = b.iter().sum::<f64>() / b.len() as f64;
+	let mut covariance = 0.0;
+	let mut variance_a = 0.0;
+	let mut variance_b = 0.0;
+	for (a, b) in a.iter().zip(b) {
+		covariance += (a - mean_a) * (b - mean_b);
+		variance_a += (a - mean_a).powi(2);
+		variance_b += (b - mean_b).powi(2);
+	}
+	if variance_a == 0.0 || variance_b == 0.0 {
+		// silence doesn't fit any key
+		return 0.0;
+	}
+	covariance / (variance_a * variance_b).sqrt()
+}
+
+fn best_key(correlations: &[f64; 24]) -> KeyEstimate {
+	let (key, correlation) = correlations
+		.iter()
+		.copied()
+		.enumerate()
+		.max_by(|(_, a), (_, b)| a.total_cmp(b))
+		.unwrap();
+	KeyEstimate {
+		tonic: key % 12,
+		minor: key >= 12,
+		correlation,
+	}
+}
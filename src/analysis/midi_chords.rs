@@ -0,0 +1,337 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::Context;
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+use crate::{
+	music_theory::{key_name_from_fifths, pitch_class_name, spell_pitch_class, CHORD_QUALITIES},
+	track_info::TrackInfo,
+	user_track_info::{Change, UserTrackInfo},
+};
+
+use super::{beat_grid, build_track_info, state_changes};
+
+/// The MIDI channel (counting from 0) used for drums in General MIDI.
+const DRUM_CHANNEL: u8 = 9;
+/// The tempo of MIDI files without tempo events, in microseconds per beat.
+const DEFAULT_TEMPO: u32 = 500_000;
+/// Added to the score of chords whose root is the lowest note, so
+/// chords with the same notes (like C6 and Am7) are named after
+/// the bass.
+const ROOT_IN_BASS_BONUS: f64 = 0.05;
+/// Subtracted from the score of a chord for each of its notes that
+/// isn't playing.
+const MISSING_NOTE_PENALTY: f64 = 0.1;
+/// Subtracted from the score of a chord for each note it has beyond
+/// three, so simpler chords are preferred.
+const EXTRA_NOTE_PENALTY: f64 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MidiChordSettings {
+	/// Whether to infer one chord per bar instead of one per beat.
+	pub one_chord_per_bar: bool,
+}
+
+/// Reads the notes in a MIDI file and infers the chord playing on each
+/// beat (or bar) of the track info's tempo map, replacing the chords in
+/// the track info.
+///
+/// The MIDI file should line up with the song's audio. Chords are spelled
+/// relative to the file's key signature, or the track info's key if the
+/// file doesn't have one.
+pub fn infer_midi_chords(
+	midi: &[u8],
+	user_track_info: &UserTrackInfo,
+	song_duration: Duration,
+	settings: MidiChordSettings,
+) -> anyhow::Result<UserTrackInfo> {
+	let smf = Smf::parse(midi).context("error parsing MIDI file")?;
+	let (notes, key_signatures) = read_midi(&smf);
+	let track_info = TrackInfo::new(user_track_info);
+	let window_starts = beat_grid(&track_info, song_duration)
+		.into_iter()
+		.filter(|beat| beat.downbeat || !settings.one_chord_per_bar)
+		.map(|beat| beat.timestamp)
+		.collect::<Vec<_>>();
+	let mut changes = state_changes(&track_info)
+		.into_iter()
+		.map(|(timestamp, change)| {
+			(
+				timestamp,
+				Change {
+					chord: None,
+					..change
+				},
+			)
+		})
+		.collect::<Vec<_>>();
+	for (i, start) in window_starts.iter().enumerate() {
+		let end = window_starts.get(i + 1).copied().unwrap_or(song_duration);
+		let key = key_signatures
+			.iter()
+			.rev()
+			.find(|(time, _)| *time <= start.as_secs_f64())
+			.map(|(_, key)| key.clone())
+			.or_else(|| track_info.music_state(*start).music_state.key.clone());
+		let chord = infer_chord(
+			&notes,
+			start.as_secs_f64(),
+			end.as_secs_f64(),
+			key.as_deref(),
+		);
+		changes.push((
+			*start,
+			Change {
+				chord: Some(chord),
+				..Default::default()
+			},
+		));
+	}
+	build_track_info(user_track_info.ticks_per_beat, changes)
+}
+
+struct Note {
+	/// The start time in seconds.
+	start: f64,
+	/// The end time in seconds.
+	end: f64,
+	pitch: u8,
+	velocity: u8,
+}
+
+/// Returns the (non-drum) notes in a MIDI file and the times (in
+/// seconds) and names of its key signatures.
+fn read_midi(smf: &Smf) -> (Vec<Note>, Vec<(f64, String)>) {
+	// (tick, channel, pitch, velocity if it's a note on)
+	let mut note_events = vec![];
+	// (tick, microseconds per beat)
+	let mut tempo_changes = vec![];
+	let mut key_signatures = vec![];
+	for track in &smf.tracks {
+		let mut tick = 0u64;
+		for event in track {
+			tick += event.delta.as_int() as u64;
+			match event.kind {
+				TrackEventKind::Midi { channel, message } => match message {
+					MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+						note_events.push((
+							tick,
+							channel.as_int(),
+							key.as_int(),
+							Some(vel.as_int()),
+						));
+					}
+					MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+						note_events.push((tick, channel.as_int(), key.as_int(), None));
+					}
+					_ => {}
+				},
+				TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+					tempo_changes.push((tick, tempo.as_int()));
+				}
+				TrackEventKind::Meta(MetaMessage::KeySignature(fifths, minor)) => {
+					if let Some(key) = key_name_from_fifths(fifths.into(), minor) {
+						key_signatures.push((tick, key));
+					}
+				}
+				_ => {}
+			}
+		}
+	}
+	tempo_changes.sort_by_key(|(tick, _)| *tick);
+	// events are read track by track, so key signatures from different
+	// tracks can be out of order
+	key_signatures.sort_by_key(|(tick, _)| *tick);
+	let seconds_at = |tick: u64| match smf.header.timing {
+		Timing::Metrical(ticks_per_beat) => {
+			let ticks_per_beat = ticks_per_beat.as_int().max(1) as f64;
+			let mut seconds = 0.0;
+			let mut tempo_start = 0;
+			let mut tempo = DEFAULT_TEMPO;
+			for &(change_tick, new_tempo) in &tempo_changes {
+				if change_tick >= tick {
+					break;
+				}
+				seconds += (change_tick - tempo_start) as f64 * tempo as f64
+					/ 1_000_000.0 / ticks_per_beat;
+				tempo_start = change_tick;
+				tempo = new_tempo;
+			}
+			seconds + (tick - tempo_start) as f64 * tempo as f64 / 1_000_000.0 / ticks_per_beat
+		}
+		Timing::Timecode(fps, ticks_per_frame) => {
+			tick as f64 / (fps.as_f32() as f64 * ticks_per_frame.max(1) as f64)
+		}
+	};
+	// note offs go first so a note that's played again right as it
+	// ends doesn't end the new note
+	note_events.sort_by_key(|(tick, _, _, velocity)| (*tick, velocity.is_some()));
+	let last_tick = note_events.last().map_or(0, |(tick, ..)| *tick);
+	let mut held_notes: HashMap<(u8, u8), Vec<(u64, u8)>> = HashMap::new();
+	let mut notes = vec![];
+	for (tick, channel, pitch, velocity) in note_events {
+		if channel == DRUM_CHANNEL {
+			continue;
+		}
+		let held = held_notes.entry((channel, pitch)).or_default();
+		match velocity {
+			Some(velocity) => held.push((tick, velocity)),
+			None => {
+				if !held.is_empty() {
+					let (start_tick, velocity) = held.remove(0);
+					notes.push(Note {
+						start: seconds_at(start_tick),
+						end: seconds_at(tick),
+						pitch,
+						velocity,
+					});
+				}
+			}
+		}
+	}
+	// notes that never end last until the end of the file
+	for ((_, pitch), held) in held_notes {
+		for (start_tick, velocity) in held {
+			notes.push(Note {
+				start: seconds_at(start_tick),
+				end: seconds_at(last_tick),
+				pitch,
+				velocity,
+			});
+		}
+	}
+	let key_signatures = key_signatures
+		.into_iter()
+		.map(|(tick, key)| (seconds_at(tick), key))
+		.collect::<Vec<_>>();
+	(notes, key_signatures)
+}
+
+/// Names the chord that best fits the notes sounding between two times
+/// (in seconds), or returns `None` if no notes are sounding.
+fn infer_chord(notes: &[Note], start: f64, end: f64, key: Option<&str>) -> Option<String> {
+	let mut weights = [0.0; 12];
+	// (pitch, time sounding)
+	let mut sounding = vec![];
+	for note in notes {
+		let overlap = note.end.min(end) - note.start.max(start);
+		if overlap <= 0.0 {
+			continue;
+		}
+		weights[note.pitch as usize % 12] += overlap * note.velocity as f64;
+		sounding.push((note.pitch, overlap));
+	}
+	let total_weight = weights.iter().sum::<f64>();
+	if total_weight <= 0.0 {
+		return None;
+	}
+	for weight in &mut weights {
+		*weight /= total_weight;
+	}
+	// ignore short passing notes when looking for the bass note
+	let longest = sounding
+		.iter()
+		.map(|(_, overlap)| *overlap)
+		.fold(0.0, f64::max);
+	let bass = sounding
+		.iter()
+		.filter(|(_, overlap)| *overlap >= longest / 4.0)
+		.map(|(pitch, _)| *pitch)
+		.min()?;
+	let bass = bass as usize % 12;
+	let (root, suffix) = (0..12)
+		.flat_map(|root| {
+			CHORD_QUALITIES
+				.iter()
+				.map(move |(suffix, intervals)| (root, *suffix, *intervals))
+		})
+		.map(|(root, suffix, intervals)| {
+			let covered = intervals
+				.iter()
+				.map(|interval| weights[(root + interval) % 12])
+				.sum::<f64>();
+			let missing = intervals
+				.iter()
+				.filter(|interval| weights[(root + *interval) % 12] == 0.0)
+				.count();
+			let mut score = covered
+				- (1.0 - covered)
+				- missing as f64 * MISSING_NOTE_PENALTY
+				- intervals.len().saturating_sub(3) as f64 * EXTRA_NOTE_PENALTY;
+			if root == bass {
+				score += ROOT_IN_BASS_BONUS;
+			}
+			(root, suffix, score)
+		})
+		.max_by(|(_, _, a), (_, _, b)| a.total_cmp(b))
+		.map(|(root, suffix, _)| (root, suffix))?;
+	let name = |pitch_class: usize| match key {
+		Some(key) => spell_pitch_class(pitch_class, key),
+		None => pitch_class_name(pitch_class, false).to_string(),
+	};
+	let mut chord = format!("{}{}", name(root), suffix);
+	if bass != root {
+		chord.push('/');
+		chord.push_str(&name(bass));
+	}
+	Some(chord)
+}
+
+#[cfg(test)]
+mod tests {
+	use midly::{Format, Header, MetaMessage, TrackEvent};
+
+	use super::*;
+
+	fn note(start: f64, end: f64, pitch: u8) -> Note {
+		Note {
+			start,
+			end,
+			pitch,
+			velocity: 100,
+		}
+	}
+
+	#[test]
+	fn infers_chords() {
+		// C E G
+		let notes = [note(0.0, 1.0, 48), note(0.0, 1.0, 52), note(0.0, 1.0, 55)];
+		assert_eq!(infer_chord(&notes, 0.0, 1.0, None).as_deref(), Some("C"));
+		// the same notes over E
+		let notes = [note(0.0, 1.0, 40), note(0.0, 1.0, 48), note(0.0, 1.0, 55)];
+		assert_eq!(infer_chord(&notes, 0.0, 1.0, None).as_deref(), Some("C/E"));
+		// A C E
+		let notes = [note(0.0, 1.0, 45), note(0.0, 1.0, 48), note(0.0, 1.0, 52)];
+		assert_eq!(infer_chord(&notes, 0.0, 1.0, None).as_deref(), Some("Am"));
+		assert_eq!(infer_chord(&notes, 1.0, 2.0, None), None);
+	}
+
+	#[test]
+	fn spells_chords_in_the_key() {
+		// Db F Ab
+		let notes = [note(0.0, 1.0, 49), note(0.0, 1.0, 53), note(0.0, 1.0, 56)];
+		assert_eq!(infer_chord(&notes, 0.0, 1.0, None).as_deref(), Some("C#"));
+		assert_eq!(
+			infer_chord(&notes, 0.0, 1.0, Some("Ab")).as_deref(),
+			Some("Db")
+		);
+	}
+
+	#[test]
+	fn sorts_key_signatures_from_every_track() {
+		let key_signature = |delta: u32, fifths: i8| TrackEvent {
+			delta: delta.into(),
+			kind: TrackEventKind::Meta(MetaMessage::KeySignature(fifths, false)),
+		};
+		let smf = Smf {
+			header: Header::new(Format::Parallel, Timing::Metrical(480.into())),
+			tracks: vec![vec![key_signature(960, 2)], vec![key_signature(0, -2)]],
+		};
+		let (_, key_signatures) = read_midi(&smf);
+		// 960 ticks is 2 beats at the default 120 BPM
+		assert_eq!(
+			key_signatures,
+			vec![(0.0, "Bb".to_string()), (1.0, "D".to_string())]
+		);
+	}
+}
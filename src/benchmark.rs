@@ -0,0 +1,99 @@
+use std::{
+	path::Path,
+	time::{Duration, Instant, SystemTime},
+};
+
+use micro::{
+	graphics::{Canvas, CanvasSettings},
+	Context,
+};
+
+use crate::{
+	loaded_project::LoadedProject, rendering_state::RenderingState,
+	widgets::set_text_cache_enabled, MainState, BASE_RESOLUTION,
+};
+
+/// Renders the first `num_frames` frames of a project the same way
+/// exporting does, including the shader passes and post-processing,
+/// once with text caching disabled and once with it enabled, and prints
+/// how long the frames took.
+///
+/// The frames are rendered once before timing either pass so shader
+/// compilation and GPU warm-up don't count against the first one.
+pub fn run_benchmark(
+	ctx: &mut Context,
+	project_path: impl AsRef<Path>,
+	num_frames: u32,
+) -> anyhow::Result<()> {
+	let mut loaded_project = LoadedProject::load(ctx, project_path)?;
+	let canvas = Canvas::new(ctx, BASE_RESOLUTION, CanvasSettings::default());
+	let mut canvas_read_buffer = vec![0; (BASE_RESOLUTION.x * BASE_RESOLUTION.y * 4) as usize];
+	set_text_cache_enabled(false);
+	render_frames(
+		ctx,
+		&mut loaded_project,
+		&canvas,
+		&mut canvas_read_buffer,
+		num_frames,
+	)?;
+	for (label, text_cache_enabled) in
+		[("text cache disabled", false), ("text cache enabled", true)]
+	{
+		set_text_cache_enabled(text_cache_enabled);
+		let mut frame_times = render_frames(
+			ctx,
+			&mut loaded_project,
+			&canvas,
+			&mut canvas_read_buffer,
+			num_frames,
+		)?;
+		print_frame_times(label, &mut frame_times);
+	}
+	set_text_cache_enabled(true);
+	Ok(())
+}
+
+/// Renders the first `num_frames` frames and returns how long each
+/// one took.
+fn render_frames(
+	ctx: &mut Context,
+	loaded_project: &mut LoadedProject,
+	canvas: &Canvas,
+	canvas_read_buffer: &mut [u8],
+	num_frames: u32,
+) -> anyhow::Result<Vec<Duration>> {
+	let mut frame_times = vec![];
+	let start_date = SystemTime::now();
+	for frame in 0..num_frames {
+		let start_time = Instant::now();
+		RenderingState::send_frame_uniforms(ctx, loaded_project, frame, start_date)?;
+		let output_canvas = MainState::draw_frame(
+			ctx,
+			loaded_project,
+			canvas,
+			RenderingState::frame_time(frame),
+		)?;
+		output_canvas.read(canvas_read_buffer);
+		frame_times.push(start_time.elapsed());
+	}
+	Ok(frame_times)
+}
+
+fn print_frame_times(label: &str, frame_times: &mut [Duration]) {
+	if frame_times.is_empty() {
+		return;
+	}
+	frame_times.sort();
+	let total: Duration = frame_times.iter().sum();
+	let mean = total / frame_times.len() as u32;
+	let median = frame_times[frame_times.len() / 2];
+	let p95 = frame_times[(frame_times.len() * 95 / 100).min(frame_times.len() - 1)];
+	println!(
+		"{}: {} frames, mean {:.3}ms, median {:.3}ms, p95 {:.3}ms",
+		label,
+		frame_times.len(),
+		mean.as_secs_f64() * 1000.0,
+		median.as_secs_f64() * 1000.0,
+		p95.as_secs_f64() * 1000.0,
+	);
+}
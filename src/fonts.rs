@@ -0,0 +1,259 @@
+use std::{
+	borrow::Cow,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context as AnyhowContext};
+use micro::{
+	graphics::text::{Font, FontSettings},
+	Context,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{FLAT_SYMBOL, SHARP_SYMBOL};
+
+/// Fonts compiled into the executable so that the default theme
+/// works even if the `resources` folder can't be found.
+const EMBEDDED_FONTS: &[(&str, &str, &[u8])] = &[
+	(
+		"Monoid",
+		"Regular",
+		include_bytes!("../resources/Monoid-Regular.ttf"),
+	),
+	(
+		"Monoid",
+		"Bold",
+		include_bytes!("../resources/Monoid-Bold.ttf"),
+	),
+	(
+		"Monoid",
+		"Italic",
+		include_bytes!("../resources/Monoid-Italic.ttf"),
+	),
+];
+const FONT_FILE_EXTENSIONS: &[&str] = &["ttf", "otf"];
+
+pub struct Fonts {
+	pub small: Font,
+	pub medium: Font,
+	pub large: Font,
+	pub music_large: Font,
+	pub music_medium: Font,
+}
+
+impl Fonts {
+	pub fn new(
+		ctx: &mut Context,
+		settings: &FontsSettings,
+		project_dir: Option<&Path>,
+	) -> anyhow::Result<Self> {
+		let label_data = settings.label.load(project_dir)?;
+		let text_data = settings.text.load(project_dir)?;
+		let music_data = settings.music.load(project_dir)?;
+		let music_chars = FLAT_SYMBOL.to_string() + SHARP_SYMBOL;
+		check_glyphs(&settings.music, &music_data, &music_chars)?;
+		let sizes = settings.sizes;
+		Ok(Self {
+			small: Font::from_bytes(
+				ctx,
+				&label_data,
+				FontSettings {
+					scale: sizes.small,
+					..Default::default()
+				},
+			)?,
+			medium: Font::from_bytes(
+				ctx,
+				&text_data,
+				FontSettings {
+					scale: sizes.medium,
+					..Default::default()
+				},
+			)?,
+			large: Font::from_bytes(
+				ctx,
+				&text_data,
+				FontSettings {
+					scale: sizes.large,
+					..Default::default()
+				},
+			)?,
+			music_large: Font::from_bytes(
+				ctx,
+				&music_data,
+				FontSettings {
+					scale: sizes.large,
+					chars: music_chars.clone(),
+					..Default::default()
+				},
+			)?,
+			music_medium: Font::from_bytes(
+				ctx,
+				&music_data,
+				FontSettings {
+					scale: sizes.medium,
+					chars: music_chars,
+					..Default::default()
+				},
+			)?,
+		})
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FontsSettings {
+	/// The font used for panel labels.
+	pub label: FontFace,
+	/// The font used for widget contents.
+	pub text: FontFace,
+	/// The font used for flat and sharp symbols.
+	pub music: FontFace,
+	pub sizes: FontSizes,
+}
+
+impl Default for FontsSettings {
+	fn default() -> Self {
+		Self {
+			label: FontFace::new("Monoid", "Italic"),
+			text: FontFace::new("Monoid", "Bold"),
+			music: FontFace::new("Monoid", "Bold"),
+			sizes: FontSizes::default(),
+		}
+	}
+}
+
+/// A font, either found by family and style or loaded from
+/// an explicit path.
+///
+/// A face with family `Monoid` and style `Bold` is looked for as
+/// `Monoid-Bold.ttf` or `Monoid-Bold.otf` in the project folder and in
+/// the `resources` folder next to the executable (or any of its parent
+/// folders). Faces with the style `Regular` may also be named without
+/// a suffix, e.g. `Leland.otf`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FontFace {
+	pub family: String,
+	#[serde(default = "default_style")]
+	pub style: String,
+	/// A path to the font file, relative to the project file.
+	/// Takes precedence over the family and style if set.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub path: Option<PathBuf>,
+}
+
+impl FontFace {
+	pub fn new(family: impl Into<String>, style: impl Into<String>) -> Self {
+		Self {
+			family: family.into(),
+			style: style.into(),
+			path: None,
+		}
+	}
+
+	fn load(&self, project_dir: Option<&Path>) -> anyhow::Result<Cow<'static, [u8]>> {
+		if let Some(path) = &self.path {
+			let full_path = match project_dir {
+				Some(project_dir) => project_dir.join(path),
+				None => path.clone(),
+			};
+			return std::fs::read(&full_path)
+				.map(Cow::Owned)
+				.with_context(|| format!("error loading font {}", full_path.display()));
+		}
+		let search_dirs = font_search_dirs(project_dir);
+		for dir in &search_dirs {
+			for file_name in self.file_names() {
+				let path = dir.join(file_name);
+				if path.is_file() {
+					return std::fs::read(&path)
+						.map(Cow::Owned)
+						.with_context(|| format!("error loading font {}", path.display()));
+				}
+			}
+		}
+		if let Some((_, _, data)) = EMBEDDED_FONTS.iter().find(|(family, style, _)| {
+			family.eq_ignore_ascii_case(&self.family) && style.eq_ignore_ascii_case(&self.style)
+		}) {
+			return Ok(Cow::Borrowed(*data));
+		}
+		Err(anyhow!(
+			"could not find the font {} {}. Searched in:\n{}",
+			self.family,
+			self.style,
+			search_dirs
+				.iter()
+				.map(|dir| dir.display().to_string())
+				.collect::<Vec<_>>()
+				.join("\n")
+		))
+	}
+
+	fn file_names(&self) -> Vec<String> {
+		let mut stems = vec![format!("{}-{}", self.family, self.style)];
+		if self.style.eq_ignore_ascii_case("Regular") {
+			stems.push(self.family.clone());
+		}
+		stems
+			.iter()
+			.flat_map(|stem| {
+				FONT_FILE_EXTENSIONS
+					.iter()
+					.map(move |extension| format!("{}.{}", stem, extension))
+			})
+			.collect()
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FontSizes {
+	pub small: f32,
+	pub medium: f32,
+	pub large: f32,
+}
+
+impl Default for FontSizes {
+	fn default() -> Self {
+		Self {
+			small: 48.0,
+			medium: 64.0,
+			large: 128.0,
+		}
+	}
+}
+
+fn font_search_dirs(project_dir: Option<&Path>) -> Vec<PathBuf> {
+	let mut dirs = vec![];
+	if let Some(project_dir) = project_dir {
+		dirs.push(project_dir.to_path_buf());
+		dirs.push(project_dir.join("resources"));
+	}
+	if let Some(exe_dir) = std::env::current_exe()
+		.ok()
+		.and_then(|exe_path| exe_path.parent().map(Path::to_path_buf))
+	{
+		dirs.extend(exe_dir.ancestors().map(|dir| dir.join("resources")));
+	}
+	dirs
+}
+
+fn check_glyphs(face: &FontFace, data: &[u8], chars: &str) -> anyhow::Result<()> {
+	let font = fontdue::Font::from_bytes(data, fontdue::FontSettings::default())
+		.map_err(|err| anyhow!("error loading font {} {}: {}", face.family, face.style, err))?;
+	for char in chars.chars() {
+		if font.lookup_glyph_index(char) == 0 {
+			bail!(
+				"the font {} {} does not have a glyph for {} (U+{:04X})",
+				face.family,
+				face.style,
+				char,
+				char as u32
+			);
+		}
+	}
+	Ok(())
+}
+
+fn default_style() -> String {
+	"Regular".to_string()
+}
@@ -0,0 +1,191 @@
+pub mod chordpro;
+pub mod markers;
+pub mod musicxml;
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+
+use crate::{
+	music_state::MusicState,
+	user_track_info::{Change, UserTrackInfo},
+};
+
+/// Builds up a [`UserTrackInfo`] from events in the order they happen
+/// in a song.
+///
+/// Importers set fields on [`TrackInfoBuilder::change`] for anything
+/// that changes at the current position, then move forward with
+/// [`TrackInfoBuilder::advance`] or [`TrackInfoBuilder::advance_to`].
+pub struct TrackInfoBuilder {
+	initial_state: MusicState,
+	ticks_per_beat: u32,
+	changes: Vec<Change>,
+	/// The state after all of the changes so far (not including
+	/// the pending change).
+	current_state: MusicState,
+	pending_change: Change,
+	ticks_since_last_change: u32,
+	/// The time of the current position in the song.
+	timestamp: Duration,
+}
+
+impl TrackInfoBuilder {
+	pub fn new(ticks_per_beat: u32) -> Self {
+		let initial_state = MusicState {
+			bpm: 120.0,
+			bpm_hidden: false,
+			time_signature: None,
+			key: None,
+			chord: None,
+			section: None,
+			image_visibility: Default::default(),
+		};
+		Self {
+			current_state: initial_state.clone(),
+			initial_state,
+			ticks_per_beat,
+			changes: vec![],
+			pending_change: Change::default(),
+			ticks_since_last_change: 0,
+			timestamp: Duration::ZERO,
+		}
+	}
+
+	pub fn ticks_per_beat(&self) -> u32 {
+		self.ticks_per_beat
+	}
+
+	/// Returns the change that will happen at the current position.
+	pub fn change(&mut self) -> &mut Change {
+		&mut self.pending_change
+	}
+
+	/// Returns the music state at the current position, including
+	/// the pending change.
+	pub fn state(&self) -> MusicState {
+		self.current_state.changed(&self.pending_change)
+	}
+
+	/// Moves the current position forward by the given number of ticks.
+	pub fn advance(&mut self, ticks: u32) -> anyhow::Result<()> {
+		if ticks == 0 {
+			return Ok(());
+		}
+		let timestamp = self
+			.tick_duration()?
+			.checked_mul(ticks)
+			.and_then(|duration| self.timestamp.checked_add(duration))
+			.ok_or_else(|| anyhow!("the song is too long"))?;
+		self.flush();
+		self.ticks_since_last_change += ticks;
+		self.timestamp = timestamp;
+		Ok(())
+	}
+
+	/// Moves the current position forward to the tick closest to the
+	/// given time, based on the tempo at the current position. Does
+	/// nothing if the time isn't after the current position.
+	pub fn advance_to(&mut self, timestamp: Duration) -> anyhow::Result<()> {
+		let seconds = timestamp.saturating_sub(self.timestamp).as_secs_f64();
+		let ticks = (seconds / self.tick_duration()?.as_secs_f64()).round() as u32;
+		self.advance(ticks)
+	}
+
+	pub fn build(mut self) -> UserTrackInfo {
+		self.flush();
+		UserTrackInfo {
+			initial_state: self.initial_state,
+			ticks_per_beat: self.ticks_per_beat,
+			changes: self.changes,
+		}
+	}
+
+	fn flush(&mut self) {
+		let change = std::mem::take(&mut self.pending_change);
+		if change == Change::default() {
+			return;
+		}
+		self.current_state = self.current_state.changed(&change);
+		// changes at the very start of the song are part of the
+		// initial state
+		if self.changes.is_empty() && self.ticks_since_last_change == 0 {
+			self.initial_state = self.current_state.clone();
+			return;
+		}
+		self.changes.push(Change {
+			after: self.ticks_since_last_change,
+			..change
+		});
+		self.ticks_since_last_change = 0;
+	}
+
+	/// Returns the length of a tick at the current position, or an error
+	/// if the tempo isn't a positive, finite number.
+	fn tick_duration(&self) -> anyhow::Result<Duration> {
+		let bpm = self.state().bpm;
+		Duration::try_from_secs_f64(60.0 / bpm / self.ticks_per_beat as f64)
+			.ok()
+			.filter(|duration| !duration.is_zero())
+			.ok_or_else(|| anyhow!("invalid tempo {}", bpm))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn changes_at_the_start_are_part_of_the_initial_state() {
+		let mut builder = TrackInfoBuilder::new(4);
+		builder.change().bpm = Some(90.0);
+		builder.change().chord = Some(Some("C".to_string()));
+		builder.advance(8).unwrap();
+		builder.change().chord = Some(Some("G".to_string()));
+		builder.advance(4).unwrap();
+		let user_track_info = builder.build();
+		assert_eq!(user_track_info.initial_state.bpm, 90.0);
+		assert_eq!(user_track_info.initial_state.chord.as_deref(), Some("C"));
+		assert_eq!(
+			user_track_info.changes,
+			vec![Change {
+				after: 8,
+				chord: Some(Some("G".to_string())),
+				..Default::default()
+			}]
+		);
+	}
+
+	#[test]
+	fn advance_to_rounds_to_the_nearest_tick() {
+		// at 120 BPM and 2 ticks per beat, each tick is 0.25 seconds
+		let mut builder = TrackInfoBuilder::new(2);
+		builder.advance_to(Duration::from_secs_f64(1.1)).unwrap();
+		builder.change().key = Some(Some("D".to_string()));
+		builder.advance_to(Duration::from_secs(2)).unwrap();
+		builder.change().key = Some(Some("E".to_string()));
+		let user_track_info = builder.build();
+		assert_eq!(
+			user_track_info
+				.changes
+				.iter()
+				.map(|change| change.after)
+				.collect::<Vec<_>>(),
+			vec![4, 4]
+		);
+	}
+
+	#[test]
+	fn rejects_invalid_tempos() {
+		for bpm in [0.0, -5.0, f64::NAN, f64::INFINITY] {
+			let mut builder = TrackInfoBuilder::new(1);
+			builder.change().bpm = Some(bpm);
+			assert!(builder.advance(1).is_err(), "{}", bpm);
+			assert!(
+				builder.advance_to(Duration::from_secs(1)).is_err(),
+				"{}",
+				bpm
+			);
+		}
+	}
+}
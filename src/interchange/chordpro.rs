@@ -0,0 +1,298 @@
+use std::{fmt::Write, time::Duration};
+
+use anyhow::Context;
+
+use crate::{
+	music_state::MusicState, music_theory::TimeSignature, track_info::TrackInfo,
+	user_track_info::UserTrackInfo,
+};
+
+use super::TrackInfoBuilder;
+
+/// Chord names that mean no chord is playing.
+const NO_CHORD_NAMES: [&str; 3] = ["N.C.", "NC", "N.C"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChordProSettings {
+	/// The number of beats in a bar when the song doesn't have
+	/// a `{time}` directive.
+	pub bar_length: u32,
+}
+
+impl Default for ChordProSettings {
+	fn default() -> Self {
+		Self { bar_length: 4 }
+	}
+}
+
+/// Reads a ChordPro song into track info.
+///
+/// ChordPro doesn't say how long each chord lasts, so every chord is
+/// treated as one bar long. `{key}`, `{tempo}` and `{time}` directives and
+/// section environments like `{start_of_verse: Verse 1}` take effect at
+/// the next chord.
+pub fn import_chordpro(
+	chordpro: &str,
+	settings: ChordProSettings,
+) -> anyhow::Result<UserTrackInfo> {
+	let mut builder = TrackInfoBuilder::new(1);
+	for (line_index, line) in chordpro.lines().enumerate() {
+		let line = line.trim();
+		if line.starts_with('#') {
+			continue;
+		}
+		if let Some(directive) = line
+			.strip_prefix('{')
+			.and_then(|line| line.strip_suffix('}'))
+		{
+			apply_directive(&mut builder, directive)
+				.with_context(|| format!("error on line {}", line_index + 1))?;
+			continue;
+		}
+		let mut rest = line;
+		while let Some(chord_start) = rest.find('[') {
+			let Some(chord_length) = rest[chord_start..].find(']') else {
+				break;
+			};
+			let chord = rest[chord_start + 1..chord_start + chord_length].trim();
+			rest = &rest[chord_start + chord_length + 1..];
+			// chords starting with * are annotations, not chords
+			if chord.is_empty() || chord.starts_with('*') {
+				continue;
+			}
+			builder.change().chord = Some(if NO_CHORD_NAMES.contains(&chord) {
+				None
+			} else {
+				Some(chord.to_string())
+			});
+			let beats_per_bar = builder
+				.state()
+				.time_signature
+				.map_or(settings.bar_length, |time_signature| time_signature.top);
+			let ticks = beats_per_bar.max(1) * builder.ticks_per_beat();
+			builder
+				.advance(ticks)
+				.with_context(|| format!("error on line {}", line_index + 1))?;
+		}
+	}
+	Ok(builder.build())
+}
+
+fn apply_directive(builder: &mut TrackInfoBuilder, directive: &str) -> anyhow::Result<()> {
+	let (name, value) = match directive.split_once(':') {
+		Some((name, value)) => (name.trim(), Some(value.trim())),
+		None => (directive.trim(), None),
+	};
+	let value = value.filter(|value| !value.is_empty());
+	match (name.to_lowercase().as_str(), value) {
+		("key", Some(key)) => {
+			builder.change().key = Some(Some(key.to_string()));
+		}
+		("tempo", Some(tempo)) => {
+			let bpm = tempo
+				.parse::<f64>()
+				.ok()
+				.filter(|bpm| bpm.is_finite() && *bpm > 0.0)
+				.with_context(|| format!("invalid tempo \"{}\"", tempo))?;
+			builder.change().bpm = Some(bpm);
+		}
+		("time", Some(time_signature)) => {
+			builder.change().time_signature = Some(Some(TimeSignature::try_from(time_signature)?));
+		}
+		("sov", label) => start_section(builder, "verse", label),
+		("soc", label) => start_section(builder, "chorus", label),
+		("sob", label) => start_section(builder, "bridge", label),
+		("eov" | "eoc" | "eob", _) => builder.change().section = Some(None),
+		(name, label) => {
+			if let Some(environment) = name.strip_prefix("start_of_") {
+				start_section(builder, environment, label);
+			} else if name.starts_with("end_of_") {
+				builder.change().section = Some(None);
+			}
+			// other directives (like titles and comments) don't
+			// affect the track info
+		}
+	}
+	Ok(())
+}
+
+fn start_section(builder: &mut TrackInfoBuilder, environment: &str, label: Option<&str>) {
+	let section = label.map(str::to_string).unwrap_or_else(|| {
+		let mut chars = environment.chars();
+		chars
+			.next()
+			.map(|first| first.to_uppercase().chain(chars).collect())
+			.unwrap_or_default()
+	});
+	builder.change().section = Some(Some(section));
+}
+
+/// Writes track info as a ChordPro chart.
+///
+/// Each chord is repeated once per bar it lasts, so importing the
+/// chart again gives the same timing as long as chords change on
+/// bar lines. The last chord lasts until `song_duration`.
+pub fn export_chordpro(
+	track_info: &TrackInfo,
+	song_duration: Duration,
+	settings: ChordProSettings,
+) -> String {
+	const BARS_PER_LINE: usize = 4;
+	let mut chordpro = String::new();
+	let mut chord_line = vec![];
+	let mut open_environment: Option<&str> = None;
+	let mut previous_state: Option<&MusicState> = None;
+	for (i, timestamped_state) in track_info.music_states.iter().enumerate() {
+		let state = &timestamped_state.music_state;
+		let mut directives = vec![];
+		if previous_state.map(|previous| &previous.section) != Some(&state.section) {
+			if let Some(environment) = open_environment.take() {
+				directives.push(format!("{{end_of_{}}}", environment));
+			}
+			if let Some(section) = &state.section {
+				let environment = section_environment(section);
+				directives.push(format!("{{start_of_{}: {}}}", environment, section));
+				open_environment = Some(environment);
+			}
+		}
+		if previous_state.map(|previous| &previous.key) != Some(&state.key) {
+			if let Some(key) = &state.key {
+				directives.push(format!("{{key: {}}}", key));
+			}
+		}
+		if previous_state.map(|previous| previous.bpm) != Some(state.bpm) {
+			directives.push(format!("{{tempo: {}}}", state.bpm));
+		}
+		if previous_state.map(|previous| previous.time_signature) != Some(state.time_signature) {
+			if let Some(time_signature) = state.time_signature {
+				directives.push(format!("{{time: {}}}", time_signature));
+			}
+		}
+		if !directives.is_empty() {
+			flush_chord_line(&mut chordpro, &mut chord_line);
+			for directive in directives {
+				writeln!(chordpro, "{}", directive).unwrap();
+			}
+		}
+		let end = track_info
+			.music_states
+			.get(i + 1)
+			.map_or(song_duration, |next_state| next_state.timestamp);
+		let duration = end.saturating_sub(timestamped_state.timestamp);
+		let beats = duration.as_secs_f64() * state.bpm / 60.0;
+		let beats_per_bar = state
+			.time_signature
+			.map_or(settings.bar_length, |time_signature| time_signature.top)
+			.max(1);
+		let bars = ((beats / beats_per_bar as f64).round() as usize).max(1);
+		let chord = format!("[{}]", state.chord.as_deref().unwrap_or(NO_CHORD_NAMES[0]));
+		for _ in 0..bars {
+			chord_line.push(chord.clone());
+			if chord_line.len() == BARS_PER_LINE {
+				flush_chord_line(&mut chordpro, &mut chord_line);
+			}
+		}
+		previous_state = Some(state);
+	}
+	flush_chord_line(&mut chordpro, &mut chord_line);
+	if let Some(environment) = open_environment {
+		writeln!(chordpro, "{{end_of_{}}}", environment).unwrap();
+	}
+	chordpro
+}
+
+fn flush_chord_line(chordpro: &mut String, chord_line: &mut Vec<String>) {
+	if chord_line.is_empty() {
+		return;
+	}
+	writeln!(chordpro, "{}", chord_line.join(" ")).unwrap();
+	chord_line.clear();
+}
+
+/// Picks the ChordPro environment to use for a section based on its name.
+fn section_environment(section: &str) -> &'static str {
+	let first_word = section
+		.split_whitespace()
+		.next()
+		.unwrap_or_default()
+		.to_lowercase();
+	match first_word.as_str() {
+		"verse" => "verse",
+		"chorus" => "chorus",
+		"bridge" => "bridge",
+		_ => "part",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::user_track_info::Change;
+
+	use super::*;
+
+	const SONG: &str = "{title: Test}
+{key: G}
+{tempo: 90}
+{time: 3/4}
+{start_of_verse}
+[G]Some [D]words
+{end_of_verse}
+[N.C.]
+";
+
+	#[test]
+	fn imports_chords_and_directives() {
+		let user_track_info = import_chordpro(SONG, ChordProSettings::default()).unwrap();
+		let initial_state = &user_track_info.initial_state;
+		assert_eq!(initial_state.bpm, 90.0);
+		assert_eq!(
+			initial_state.time_signature,
+			Some(TimeSignature { top: 3, bottom: 4 })
+		);
+		assert_eq!(initial_state.key.as_deref(), Some("G"));
+		assert_eq!(initial_state.chord.as_deref(), Some("G"));
+		assert_eq!(initial_state.section.as_deref(), Some("Verse"));
+		assert_eq!(
+			user_track_info.changes,
+			vec![
+				Change {
+					after: 3,
+					chord: Some(Some("D".to_string())),
+					..Default::default()
+				},
+				Change {
+					after: 3,
+					chord: Some(None),
+					section: Some(None),
+					..Default::default()
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn rejects_invalid_tempos() {
+		for tempo in ["0", "-5", "nan", "inf", "fast"] {
+			let chordpro = format!("{{tempo: {}}}\n[C]", tempo);
+			assert!(
+				import_chordpro(&chordpro, ChordProSettings::default()).is_err(),
+				"{}",
+				tempo
+			);
+		}
+	}
+
+	#[test]
+	fn round_trips_through_export() {
+		let settings = ChordProSettings::default();
+		let user_track_info = import_chordpro(SONG, settings).unwrap();
+		let track_info = TrackInfo::new(&user_track_info);
+		// three bars of 3/4 at 90 BPM
+		let song_duration = Duration::from_secs(6);
+		let exported = export_chordpro(&track_info, song_duration, settings);
+		assert_eq!(
+			import_chordpro(&exported, settings).unwrap(),
+			user_track_info
+		);
+	}
+}
@@ -0,0 +1,456 @@
+use std::{fmt::Write, time::Duration};
+
+use anyhow::{anyhow, Context};
+
+use crate::{
+	music_state::MusicState, music_theory::TimeSignature, track_info::TrackInfo,
+	user_track_info::UserTrackInfo,
+};
+
+use super::TrackInfoBuilder;
+
+/// Ticks per beat of imported track info. Markers can be anywhere, so
+/// this is high enough that rounding them to the nearest tick isn't
+/// noticeable.
+const TICKS_PER_BEAT: u32 = 960;
+
+/// Reads markers and regions from a CSV file exported from Reaper's
+/// region/marker manager.
+///
+/// Marker names like `chord:Am7`, `key:F`, `bpm:92`, `time:3/4` or
+/// `section:Chorus` become changes at the marker's position (several
+/// can be given in one name, separated by `;`). Other markers are
+/// ignored. A section region ends its section at the end of the region.
+/// Positions have to be shown in minutes:seconds or seconds when the
+/// CSV is exported.
+pub fn import_reaper_csv(csv: &str) -> anyhow::Result<UserTrackInfo> {
+	let mut markers = vec![];
+	let mut lines = csv
+		.lines()
+		.enumerate()
+		.filter(|(_, line)| !line.trim().is_empty());
+	let Some((_, header)) = lines.next() else {
+		return import_markers(vec![]);
+	};
+	let header = split_csv_line(header);
+	let column = |name: &str| {
+		header
+			.iter()
+			.position(|column| column.eq_ignore_ascii_case(name))
+			.ok_or_else(|| anyhow!("the CSV file doesn't have a \"{}\" column", name))
+	};
+	let name_column = column("Name")?;
+	let start_column = column("Start")?;
+	let end_column = column("End").ok();
+	for (line_index, line) in lines {
+		let fields = split_csv_line(line);
+		let field = |index: usize| fields.get(index).map(String::as_str).unwrap_or_default();
+		let marker = (|| -> anyhow::Result<Marker> {
+			let start = parse_reaper_time(field(start_column))?;
+			let end = match end_column.map(field).filter(|end| !end.is_empty()) {
+				Some(end) => Some(parse_reaper_time(end)?),
+				None => None,
+			};
+			Ok(Marker {
+				start,
+				end,
+				label: field(name_column).to_string(),
+			})
+		})()
+		.with_context(|| format!("error on line {}", line_index + 1))?;
+		markers.push(marker);
+	}
+	import_markers(markers)
+}
+
+/// Reads an Audacity label track exported as a text file.
+///
+/// Labels are read the same way as Reaper markers (see
+/// [`import_reaper_csv`]), with region labels acting like regions.
+pub fn import_audacity_labels(labels: &str) -> anyhow::Result<UserTrackInfo> {
+	let mut markers = vec![];
+	for (line_index, line) in labels.lines().enumerate() {
+		// lines starting with a backslash hold the frequency range of
+		// the previous label
+		if line.trim().is_empty() || line.starts_with('\\') {
+			continue;
+		}
+		let marker = (|| -> anyhow::Result<Marker> {
+			let mut fields = line.splitn(3, '\t');
+			let start = parse_seconds(fields.next().unwrap_or_default())?;
+			let end = parse_seconds(fields.next().ok_or_else(|| anyhow!("missing end time"))?)?;
+			Ok(Marker {
+				start,
+				end: (end > start).then_some(end),
+				label: fields.next().unwrap_or_default().to_string(),
+			})
+		})()
+		.with_context(|| format!("error on line {}", line_index + 1))?;
+		markers.push(marker);
+	}
+	import_markers(markers)
+}
+
+/// Writes track info as a CSV file that can be imported into Reaper's
+/// region/marker manager.
+///
+/// Sections are written as regions and everything else as markers.
+pub fn export_reaper_csv(track_info: &TrackInfo, song_duration: Duration) -> String {
+	let mut csv = "#,Name,Start,End,Length\n".to_string();
+	let mut marker_count = 0;
+	let mut region_count = 0;
+	for marker in export_markers(track_info, song_duration) {
+		let name = quote_csv_field(&marker.label);
+		match marker.end {
+			Some(end) => {
+				region_count += 1;
+				writeln!(
+					csv,
+					"R{},{},{},{},{}",
+					region_count,
+					name,
+					format_reaper_time(marker.start),
+					format_reaper_time(end),
+					format_reaper_time(end - marker.start),
+				)
+				.unwrap();
+			}
+			None => {
+				marker_count += 1;
+				writeln!(
+					csv,
+					"M{},{},{},,",
+					marker_count,
+					name,
+					format_reaper_time(marker.start)
+				)
+				.unwrap();
+			}
+		}
+	}
+	csv
+}
+
+/// Writes track info as an Audacity label track.
+///
+/// Sections are written as region labels and everything else as
+/// point labels.
+pub fn export_audacity_labels(track_info: &TrackInfo, song_duration: Duration) -> String {
+	let mut labels = String::new();
+	for marker in export_markers(track_info, song_duration) {
+		let end = marker.end.unwrap_or(marker.start);
+		writeln!(
+			labels,
+			"{:.6}\t{:.6}\t{}",
+			marker.start.as_secs_f64(),
+			end.as_secs_f64(),
+			marker.label
+		)
+		.unwrap();
+	}
+	labels
+}
+
+/// A point marker or a region (if it has an `end`).
+struct Marker {
+	start: Duration,
+	end: Option<Duration>,
+	label: String,
+}
+
+fn import_markers(markers: Vec<Marker>) -> anyhow::Result<UserTrackInfo> {
+	let mut events = vec![];
+	for marker in markers {
+		for (name, value) in marker
+			.label
+			.split(';')
+			.filter_map(|directive| directive.split_once(':'))
+		{
+			let name = name.trim().to_lowercase();
+			let value = value.trim().to_string();
+			if name == "section" && !value.is_empty() {
+				if let Some(end) = marker.end {
+					events.push(MarkerEvent {
+						timestamp: end,
+						name: name.clone(),
+						value: String::new(),
+					});
+				}
+			}
+			events.push(MarkerEvent {
+				timestamp: marker.start,
+				name,
+				value,
+			});
+		}
+	}
+	// the end of a region should come before anything starting at the
+	// same time, so back to back sections don't cancel each other out
+	events.sort_by_key(|event| (event.timestamp, !event.value.is_empty()));
+	let mut builder = TrackInfoBuilder::new(TICKS_PER_BEAT);
+	for event in events {
+		builder.advance_to(event.timestamp)?;
+		let value = (!event.value.is_empty()).then_some(event.value);
+		match event.name.as_str() {
+			"chord" => builder.change().chord = Some(value.filter(|chord| chord != "N.C.")),
+			"key" => builder.change().key = Some(value),
+			"section" => builder.change().section = Some(value),
+			"bpm" => {
+				// markers with invalid values are skipped like markers
+				// that aren't meant for us
+				if let Some(bpm) = value
+					.and_then(|bpm| bpm.parse::<f64>().ok())
+					.filter(|bpm| bpm.is_finite() && *bpm > 0.0)
+				{
+					builder.change().bpm = Some(bpm);
+				}
+			}
+			"time" => {
+				if let Ok(time_signature) =
+					value.as_deref().map(TimeSignature::try_from).transpose()
+				{
+					builder.change().time_signature = Some(time_signature);
+				}
+			}
+			_ => {}
+		}
+	}
+	Ok(builder.build())
+}
+
+struct MarkerEvent {
+	timestamp: Duration,
+	name: String,
+	/// An empty value removes the key, chord, section or time signature.
+	value: String,
+}
+
+fn export_markers(track_info: &TrackInfo, song_duration: Duration) -> Vec<Marker> {
+	let mut markers = vec![];
+	let mut previous_state: Option<&MusicState> = None;
+	for (i, timestamped_state) in track_info.music_states.iter().enumerate() {
+		let state = &timestamped_state.music_state;
+		let start = timestamped_state.timestamp;
+		let mut push = |label: String| {
+			markers.push(Marker {
+				start,
+				end: None,
+				label,
+			})
+		};
+		if previous_state.map(|previous| previous.bpm) != Some(state.bpm) {
+			push(format!("bpm:{}", state.bpm));
+		}
+		if previous_state.map(|previous| previous.time_signature) != Some(state.time_signature) {
+			push(format!(
+				"time:{}",
+				state
+					.time_signature
+					.map(|time_signature| time_signature.to_string())
+					.unwrap_or_default()
+			));
+		}
+		if previous_state.map(|previous| &previous.key) != Some(&state.key) {
+			push(format!("key:{}", state.key.as_deref().unwrap_or_default()));
+		}
+		if previous_state.map(|previous| &previous.chord) != Some(&state.chord) {
+			push(format!(
+				"chord:{}",
+				state.chord.as_deref().unwrap_or("N.C.")
+			));
+		}
+		if previous_state.map(|previous| &previous.section) != Some(&state.section) {
+			if let Some(section) = &state.section {
+				let end = track_info.music_states[i + 1..]
+					.iter()
+					.find(|next_state| next_state.music_state.section != state.section)
+					.map_or(song_duration, |next_state| next_state.timestamp);
+				markers.push(Marker {
+					start,
+					end: Some(end.max(start)),
+					label: format!("section:{}", section),
+				});
+			}
+		}
+		previous_state = Some(state);
+	}
+	markers
+}
+
+/// Parses a position like `1:23.456`, `1:02:03.456` or `83.456`.
+fn parse_reaper_time(time: &str) -> anyhow::Result<Duration> {
+	let time = time.trim();
+	let mut seconds = 0.0;
+	for part in time.split(':') {
+		let part: f64 = part
+			.parse()
+			.with_context(|| format!("invalid position \"{}\" (positions have to be exported in minutes:seconds or seconds)", time))?;
+		seconds = seconds * 60.0 + part;
+	}
+	Duration::try_from_secs_f64(seconds).map_err(|_| anyhow!("invalid position \"{}\"", time))
+}
+
+fn parse_seconds(seconds: &str) -> anyhow::Result<Duration> {
+	let seconds = seconds.trim();
+	seconds
+		.parse::<f64>()
+		.ok()
+		.and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+		.ok_or_else(|| anyhow!("invalid time \"{}\"", seconds))
+}
+
+fn format_reaper_time(time: Duration) -> String {
+	let milliseconds = (time.as_secs_f64() * 1000.0).round() as u64;
+	format!(
+		"{}:{:02}.{:03}",
+		milliseconds / 60_000,
+		milliseconds / 1000 % 60,
+		milliseconds % 1000
+	)
+}
+
+/// Splits a line of a CSV file into fields, handling quoted fields.
+fn split_csv_line(line: &str) -> Vec<String> {
+	let mut fields = vec![];
+	let mut field = String::new();
+	let mut in_quotes = false;
+	let mut chars = line.chars().peekable();
+	while let Some(char) = chars.next() {
+		match char {
+			'"' if in_quotes && chars.peek() == Some(&'"') => {
+				field.push('"');
+				chars.next();
+			}
+			'"' => in_quotes = !in_quotes,
+			',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+			_ => field.push(char),
+		}
+	}
+	fields.push(field);
+	fields
+}
+
+fn quote_csv_field(field: &str) -> String {
+	if field.contains([',', '"']) {
+		format!("\"{}\"", field.replace('"', "\"\""))
+	} else {
+		field.to_string()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::user_track_info::Change;
+
+	use super::*;
+
+	const REAPER_CSV: &str = "#,Name,Start,End,Length
+M1,\"bpm:90;key:G\",0:00.000,,
+M2,chord:G,0:00.000,,
+R1,section:Verse,0:02.000,0:06.000,0:04.000
+M3,chord:Em,0:04.000,,
+M4,bpm:inf,0:05.000,,
+M5,bpm:0,0:05.000,,
+M6,Just a note,0:05.000,,
+";
+
+	#[test]
+	fn parses_times() {
+		assert_eq!(
+			parse_reaper_time("1:23.5").unwrap(),
+			Duration::from_secs_f64(83.5)
+		);
+		assert_eq!(
+			parse_reaper_time("1:02:03").unwrap(),
+			Duration::from_secs(3723)
+		);
+		assert_eq!(
+			parse_reaper_time("83.5").unwrap(),
+			Duration::from_secs_f64(83.5)
+		);
+		assert_eq!(
+			parse_seconds("2.25").unwrap(),
+			Duration::from_secs_f64(2.25)
+		);
+		for time in ["", "1.2.3", "-1", "inf", "nan", "1e300", "1:inf"] {
+			assert!(parse_reaper_time(time).is_err(), "{}", time);
+			assert!(parse_seconds(time).is_err(), "{}", time);
+		}
+	}
+
+	#[test]
+	fn imports_reaper_markers() {
+		let user_track_info = import_reaper_csv(REAPER_CSV).unwrap();
+		let initial_state = &user_track_info.initial_state;
+		assert_eq!(initial_state.bpm, 90.0);
+		assert_eq!(initial_state.key.as_deref(), Some("G"));
+		assert_eq!(initial_state.chord.as_deref(), Some("G"));
+		// the invalid bpm markers are ignored, and 2 seconds at 90 BPM
+		// is 3 beats
+		let ticks = 3 * TICKS_PER_BEAT;
+		assert_eq!(
+			user_track_info.changes,
+			vec![
+				Change {
+					after: ticks,
+					section: Some(Some("Verse".to_string())),
+					..Default::default()
+				},
+				Change {
+					after: ticks,
+					chord: Some(Some("Em".to_string())),
+					..Default::default()
+				},
+				Change {
+					after: ticks,
+					section: Some(None),
+					..Default::default()
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn imports_audacity_labels() {
+		let labels = "0.000000\t0.000000\tchord:C
+\\\t100.0\t2000.0
+1.000000\t3.000000\tsection:Intro
+";
+		let user_track_info = import_audacity_labels(labels).unwrap();
+		assert_eq!(user_track_info.initial_state.chord.as_deref(), Some("C"));
+		let sections = user_track_info
+			.changes
+			.iter()
+			.map(|change| (change.after, change.section.clone()))
+			.collect::<Vec<_>>();
+		// 1 and 2 seconds at 120 BPM
+		assert_eq!(
+			sections,
+			vec![
+				(2 * TICKS_PER_BEAT, Some(Some("Intro".to_string()))),
+				(4 * TICKS_PER_BEAT, Some(None)),
+			]
+		);
+		assert!(import_audacity_labels("inf\tinf\tchord:C").is_err());
+	}
+
+	#[test]
+	fn round_trips_through_export() {
+		let user_track_info = import_reaper_csv(REAPER_CSV).unwrap();
+		let track_info = TrackInfo::new(&user_track_info);
+		let song_duration = Duration::from_secs(8);
+		let csv = export_reaper_csv(&track_info, song_duration);
+		assert_eq!(import_reaper_csv(&csv).unwrap(), user_track_info);
+		let labels = export_audacity_labels(&track_info, song_duration);
+		assert_eq!(import_audacity_labels(&labels).unwrap(), user_track_info);
+	}
+
+	#[test]
+	fn quotes_csv_fields() {
+		let field = "section:Verse, \"quoted\"";
+		assert_eq!(
+			split_csv_line(&format!("M1,{},0:00.000", quote_csv_field(field))),
+			vec!["M1", field, "0:00.000"]
+		);
+	}
+}
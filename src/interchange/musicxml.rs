@@ -0,0 +1,593 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Context};
+use roxmltree::{Document, Node};
+
+use crate::{
+	music_theory::{key_name_from_fifths, TimeSignature},
+	user_track_info::UserTrackInfo,
+};
+
+use super::TrackInfoBuilder;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MusicXmlSettings {
+	/// The index of the part to read chords and directions from.
+	pub part_index: usize,
+}
+
+/// Reads chord symbols, key and time signatures, tempo markings and
+/// rehearsal marks from a part of a (partwise, uncompressed) MusicXML
+/// score. Repeats and voltas are played out so the result lines up
+/// with a recording of the score.
+pub fn import_musicxml(
+	musicxml: &str,
+	settings: MusicXmlSettings,
+) -> anyhow::Result<UserTrackInfo> {
+	let document = Document::parse(musicxml).context("error parsing MusicXML")?;
+	let score = document.root_element();
+	if score.tag_name().name() != "score-partwise" {
+		bail!("only partwise MusicXML scores are supported");
+	}
+	let part = children(score, "part")
+		.nth(settings.part_index)
+		.ok_or_else(|| anyhow!("the score doesn't have a part {}", settings.part_index + 1))?;
+	let measures = children(part, "measure").collect::<Vec<_>>();
+	let ticks_per_quarter = ticks_per_quarter(&measures);
+	let mut events = vec![];
+	let mut divisions = 1;
+	let mut measure_start = 0;
+	for measure_index in playback_order(&measures) {
+		let measure_length = read_measure(
+			measures[measure_index],
+			measure_start,
+			ticks_per_quarter,
+			&mut divisions,
+			&mut events,
+		)
+		.with_context(|| {
+			format!(
+				"error reading measure {}",
+				measures[measure_index]
+					.attribute("number")
+					.unwrap_or_default()
+			)
+		})?;
+		measure_start += measure_length;
+	}
+	// events are stable sorted so things at the same time stay in
+	// document order
+	events.sort_by_key(|(position, _)| *position);
+	// beats in the track info are the time signature's beat unit rather
+	// than always quarter notes, so the metronome counts correctly.
+	// this means the bpm and tick length depend on the time signature
+	let mut builder = TrackInfoBuilder::new(4 * ticks_per_quarter);
+	let mut quarter_bpm = 120.0;
+	let mut position = 0;
+	for (event_position, event) in events {
+		let beat_unit = builder
+			.state()
+			.time_signature
+			.map_or(4, |time_signature| time_signature.bottom);
+		let ticks = (event_position - position)
+			.checked_mul(beat_unit)
+			.ok_or_else(|| anyhow!("the score is too long"))?;
+		builder.advance(ticks)?;
+		position = event_position;
+		match event {
+			Event::Chord(chord) => builder.change().chord = Some(chord),
+			Event::Key(key) => builder.change().key = Some(Some(key)),
+			Event::TimeSignature(time_signature) => {
+				builder.change().time_signature = Some(Some(time_signature));
+			}
+			Event::Tempo(bpm) => quarter_bpm = bpm,
+			Event::RehearsalMark(mark) => builder.change().section = Some(Some(mark)),
+		}
+		let beat_unit = builder
+			.state()
+			.time_signature
+			.map_or(4, |time_signature| time_signature.bottom);
+		let bpm = quarter_bpm * beat_unit as f64 / 4.0;
+		if !(bpm.is_finite() && bpm > 0.0) {
+			bail!("invalid tempo {}", bpm);
+		}
+		if builder.state().bpm != bpm {
+			builder.change().bpm = Some(bpm);
+		}
+	}
+	Ok(builder.build())
+}
+
+enum Event {
+	/// A chord symbol, or `None` for "N.C.".
+	Chord(Option<String>),
+	Key(String),
+	TimeSignature(TimeSignature),
+	/// A tempo in quarter notes per minute.
+	Tempo(f64),
+	RehearsalMark(String),
+}
+
+/// Returns the number of ticks per quarter note needed to represent
+/// every position in the part exactly.
+fn ticks_per_quarter(measures: &[Node]) -> u32 {
+	measures
+		.iter()
+		.flat_map(|measure| children(*measure, "attributes"))
+		.filter_map(|attributes| child_text(attributes, "divisions"))
+		.filter_map(|divisions| divisions.parse::<u32>().ok())
+		.filter(|divisions| *divisions > 0)
+		.fold(1, |ticks, divisions| {
+			ticks / gcd(ticks, divisions) * divisions
+		})
+}
+
+/// Reads the events in a measure, returning the length of the measure
+/// in ticks.
+fn read_measure(
+	measure: Node,
+	measure_start: u32,
+	ticks_per_quarter: u32,
+	divisions: &mut u32,
+	events: &mut Vec<(u32, Event)>,
+) -> anyhow::Result<u32> {
+	// positions within the measure are in divisions, which can change
+	// in the middle of the part
+	let mut position: i64 = 0;
+	let mut measure_length: i64 = 0;
+	let to_ticks = |position: i64, divisions: u32| {
+		measure_start + (position.max(0) as u32) * ticks_per_quarter / divisions
+	};
+	for element in measure.children().filter(Node::is_element) {
+		let offset = child_text(element, "offset")
+			.and_then(|offset| offset.parse::<i64>().ok())
+			.unwrap_or(0);
+		let event_ticks = to_ticks(position + offset, *divisions);
+		match element.tag_name().name() {
+			"attributes" => {
+				if let Some(new_divisions) = child_text(element, "divisions") {
+					*divisions = new_divisions
+						.parse()
+						.ok()
+						.filter(|divisions| *divisions > 0)
+						.ok_or_else(|| anyhow!("invalid divisions \"{}\"", new_divisions))?;
+				}
+				if let Some(key) = children(element, "key").next() {
+					if let Some(key) = read_key(key)? {
+						events.push((event_ticks, Event::Key(key)));
+					}
+				}
+				if let Some(time) = children(element, "time").next() {
+					if let (Some(beats), Some(beat_type)) =
+						(child_text(time, "beats"), child_text(time, "beat-type"))
+					{
+						events.push((
+							event_ticks,
+							Event::TimeSignature(TimeSignature::try_from(
+								format!("{}/{}", beats, beat_type).as_str(),
+							)?),
+						));
+					}
+				}
+			}
+			"harmony" => events.push((event_ticks, Event::Chord(read_harmony(element)))),
+			"direction" => {
+				for rehearsal in element
+					.descendants()
+					.filter(|node| node.has_tag_name("rehearsal"))
+				{
+					if let Some(text) = rehearsal.text().map(str::trim) {
+						events.push((event_ticks, Event::RehearsalMark(text.to_string())));
+					}
+				}
+				if let Some(sound) = children(element, "sound").next() {
+					push_tempo(sound, event_ticks, events)?;
+				}
+			}
+			"sound" => push_tempo(element, event_ticks, events)?,
+			// notes in a chord start at the same time as the previous
+			// note, and grace notes don't have a duration
+			"note" if children(element, "chord").next().is_none() => {
+				position += duration(element)?;
+			}
+			"backup" => position -= duration(element)?,
+			"forward" => position += duration(element)?,
+			_ => {}
+		}
+		measure_length = measure_length.max(position);
+	}
+	Ok(to_ticks(measure_length, *divisions) - measure_start)
+}
+
+fn push_tempo(sound: Node, ticks: u32, events: &mut Vec<(u32, Event)>) -> anyhow::Result<()> {
+	if let Some(tempo) = sound.attribute("tempo") {
+		let tempo = tempo
+			.parse::<f64>()
+			.ok()
+			.filter(|tempo| tempo.is_finite())
+			.with_context(|| format!("invalid tempo \"{}\"", tempo))?;
+		if tempo > 0.0 {
+			events.push((ticks, Event::Tempo(tempo)));
+		}
+	}
+	Ok(())
+}
+
+fn duration(element: Node) -> anyhow::Result<i64> {
+	let Some(duration) = child_text(element, "duration") else {
+		return Ok(0);
+	};
+	duration
+		.parse()
+		.with_context(|| format!("invalid duration \"{}\"", duration))
+}
+
+fn read_key(key: Node) -> anyhow::Result<Option<String>> {
+	let Some(fifths) = child_text(key, "fifths") else {
+		return Ok(None);
+	};
+	let fifths = fifths
+		.parse()
+		.with_context(|| format!("invalid key signature \"{}\"", fifths))?;
+	let minor = child_text(key, "mode") == Some("minor");
+	Ok(key_name_from_fifths(fifths, minor))
+}
+
+fn read_harmony(harmony: Node) -> Option<String> {
+	let kind = children(harmony, "kind").next();
+	if kind.and_then(|kind| kind.text()).map(str::trim) == Some("none") {
+		return None;
+	}
+	let root = children(harmony, "root").next()?;
+	let mut chord = note_name(root, "root-step", "root-alter")?;
+	if let Some(kind) = kind {
+		chord.push_str(&match kind.attribute("text") {
+			Some(text) => text.to_string(),
+			None => chord_kind_suffix(kind.text().unwrap_or_default().trim()).to_string(),
+		});
+	}
+	if let Some(bass) = children(harmony, "bass").next() {
+		if let Some(bass) = note_name(bass, "bass-step", "bass-alter") {
+			chord.push('/');
+			chord.push_str(&bass);
+		}
+	}
+	Some(chord)
+}
+
+fn note_name(node: Node, step_tag: &str, alter_tag: &str) -> Option<String> {
+	let step = child_text(node, step_tag)?;
+	let alter = child_text(node, alter_tag)
+		.and_then(|alter| alter.parse::<f32>().ok())
+		.unwrap_or(0.0)
+		.round() as i32;
+	let accidental = if alter < 0 { "b" } else { "#" };
+	Some(format!(
+		"{}{}",
+		step,
+		accidental.repeat(alter.unsigned_abs() as usize)
+	))
+}
+
+/// Returns the usual chord symbol suffix for a MusicXML chord kind.
+fn chord_kind_suffix(kind: &str) -> &'static str {
+	match kind {
+		"minor" => "m",
+		"augmented" => "aug",
+		"diminished" => "dim",
+		"dominant" => "7",
+		"major-seventh" => "maj7",
+		"minor-seventh" => "m7",
+		"diminished-seventh" => "dim7",
+		"augmented-seventh" => "aug7",
+		"half-diminished" => "m7b5",
+		"major-minor" => "m(maj7)",
+		"major-sixth" => "6",
+		"minor-sixth" => "m6",
+		"dominant-ninth" => "9",
+		"major-ninth" => "maj9",
+		"minor-ninth" => "m9",
+		"dominant-11th" => "11",
+		"major-11th" => "maj11",
+		"minor-11th" => "m11",
+		"dominant-13th" => "13",
+		"major-13th" => "maj13",
+		"minor-13th" => "m13",
+		"suspended-second" => "sus2",
+		"suspended-fourth" => "sus4",
+		"power" => "5",
+		_ => "",
+	}
+}
+
+/// Returns the indices of the measures in the order they're played,
+/// following repeat barlines and first/second endings.
+fn playback_order(measures: &[Node]) -> Vec<usize> {
+	struct MeasureRepeats {
+		forward_repeat: bool,
+		/// How many times the repeated section is played, if the
+		/// measure ends with a repeat barline.
+		backward_repeat: Option<u32>,
+		/// The passes through a repeated section this measure is
+		/// played on, if it's part of an ending.
+		ending: Option<Vec<u32>>,
+		ending_stops: bool,
+	}
+
+	let mut current_ending = None;
+	let repeats = measures
+		.iter()
+		.map(|measure| {
+			let mut measure_repeats = MeasureRepeats {
+				forward_repeat: false,
+				backward_repeat: None,
+				ending: None,
+				ending_stops: false,
+			};
+			for barline in children(*measure, "barline") {
+				if let Some(repeat) = children(barline, "repeat").next() {
+					match repeat.attribute("direction") {
+						Some("forward") => measure_repeats.forward_repeat = true,
+						Some("backward") => {
+							measure_repeats.backward_repeat = Some(
+								repeat
+									.attribute("times")
+									.and_then(|times| times.parse().ok())
+									.unwrap_or(2),
+							);
+						}
+						_ => {}
+					}
+				}
+				if let Some(ending) = children(barline, "ending").next() {
+					if ending.attribute("type") == Some("start") {
+						current_ending = Some(
+							ending
+								.attribute("number")
+								.unwrap_or("1")
+								.split(|c: char| c == ',' || c.is_whitespace())
+								.filter_map(|number| number.trim().parse().ok())
+								.collect::<Vec<u32>>(),
+						);
+					}
+				}
+			}
+			measure_repeats.ending = current_ending.clone();
+			for barline in children(*measure, "barline") {
+				if let Some(ending) = children(barline, "ending").next() {
+					if matches!(ending.attribute("type"), Some("stop" | "discontinue")) {
+						measure_repeats.ending_stops = true;
+						current_ending = None;
+					}
+				}
+			}
+			measure_repeats
+		})
+		.collect::<Vec<_>>();
+
+	let mut order = vec![];
+	let mut repeat_start = 0;
+	let mut pass = 1;
+	let mut jumps_taken: HashMap<usize, u32> = HashMap::new();
+	// whether the last measure played was part of an ending
+	let mut in_ending = false;
+	let mut index = 0;
+	while index < measures.len() {
+		let measure = &repeats[index];
+		if in_ending && measure.ending.is_none() {
+			// we just left the last ending of a repeated section, even if
+			// the measure that ends it wasn't played
+			pass = 1;
+			repeat_start = index;
+		}
+		if measure.forward_repeat && pass == 1 {
+			repeat_start = index;
+		}
+		if let Some(ending) = &measure.ending {
+			if !ending.contains(&pass) {
+				index += 1;
+				continue;
+			}
+		}
+		order.push(index);
+		in_ending = measure.ending.is_some();
+		if let Some(times) = measure.backward_repeat {
+			let jumps = jumps_taken.entry(index).or_default();
+			if *jumps + 1 < times {
+				*jumps += 1;
+				pass += 1;
+				in_ending = false;
+				index = repeat_start;
+				continue;
+			}
+			*jumps = 0;
+			pass = 1;
+			repeat_start = index + 1;
+		} else if measure.ending.is_some() && measure.ending_stops {
+			// the last ending of a repeated section
+			pass = 1;
+			repeat_start = index + 1;
+		}
+		index += 1;
+	}
+	order
+}
+
+fn children<'a, 'input: 'a>(
+	node: Node<'a, 'input>,
+	tag_name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+	node.children()
+		.filter(move |child| child.has_tag_name(tag_name))
+}
+
+fn child_text<'a>(node: Node<'a, '_>, tag_name: &str) -> Option<&'a str> {
+	node.children()
+		.find(|child| child.has_tag_name(tag_name))
+		.and_then(|child| child.text())
+		.map(str::trim)
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+	if b == 0 {
+		a
+	} else {
+		gcd(b, a % b)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn score(measures: &str) -> String {
+		format!(
+			"<score-partwise><part id=\"P1\">{}</part></score-partwise>",
+			measures
+		)
+	}
+
+	fn measure(time: &str, tempo: &str, chord: &str) -> String {
+		format!(
+			r#"
+			<measure number="1">
+				<attributes>
+					<divisions>1</divisions>
+					<time><beats>{}</beats><beat-type>{}</beat-type></time>
+				</attributes>
+				<sound tempo="{}"/>
+				<harmony><root><root-step>{}</root-step></root><kind>major</kind></harmony>
+				<note><duration>4</duration></note>
+			</measure>
+			"#,
+			time.split('/').next().unwrap(),
+			time.split('/').nth(1).unwrap(),
+			tempo,
+			chord
+		)
+	}
+
+	#[test]
+	fn imports_chords_time_signatures_and_tempos() {
+		let musicxml = score(&format!(
+			"{}<measure number=\"2\">{}</measure>",
+			measure("4/4", "90", "C"),
+			r#"<harmony><root><root-step>G</root-step></root><kind>minor</kind></harmony>"#
+		));
+		let user_track_info = import_musicxml(&musicxml, MusicXmlSettings::default()).unwrap();
+		let initial_state = &user_track_info.initial_state;
+		assert_eq!(initial_state.bpm, 90.0);
+		assert_eq!(
+			initial_state.time_signature,
+			Some(TimeSignature { top: 4, bottom: 4 })
+		);
+		assert_eq!(initial_state.chord.as_deref(), Some("C"));
+		assert_eq!(user_track_info.changes.len(), 1);
+		// four quarter notes, with 4 ticks per beat
+		assert_eq!(user_track_info.changes[0].after, 16);
+		assert_eq!(
+			user_track_info.changes[0].chord,
+			Some(Some("Gm".to_string()))
+		);
+	}
+
+	#[test]
+	fn scales_tempos_to_the_beat_unit() {
+		let musicxml = score(&measure("6/8", "60", "C"));
+		let user_track_info = import_musicxml(&musicxml, MusicXmlSettings::default()).unwrap();
+		assert_eq!(user_track_info.initial_state.bpm, 120.0);
+	}
+
+	#[test]
+	fn rejects_invalid_time_signatures_and_tempos() {
+		for (time, tempo) in [("4/0", "90"), ("0/4", "90"), ("4/4", "inf"), ("4/4", "nan")] {
+			let musicxml = score(&measure(time, tempo, "C"));
+			assert!(
+				import_musicxml(&musicxml, MusicXmlSettings::default()).is_err(),
+				"{} {}",
+				time,
+				tempo
+			);
+		}
+	}
+
+	#[test]
+	fn plays_out_repeats_and_endings() {
+		let musicxml = score(
+			r#"
+			<measure number="1">
+				<barline location="left"><repeat direction="forward"/></barline>
+			</measure>
+			<measure number="2">
+				<barline location="left"><ending number="1" type="start"/></barline>
+				<barline location="right">
+					<ending number="1" type="stop"/>
+					<repeat direction="backward"/>
+				</barline>
+			</measure>
+			<measure number="3">
+				<barline location="left"><ending number="2" type="start"/></barline>
+				<barline location="right"><ending number="2" type="discontinue"/></barline>
+			</measure>
+			<measure number="4">
+				<barline location="left"><repeat direction="forward"/></barline>
+			</measure>
+			<measure number="5">
+				<barline location="left"><ending number="1" type="start"/></barline>
+				<barline location="right">
+					<ending number="1" type="stop"/>
+					<repeat direction="backward"/>
+				</barline>
+			</measure>
+			<measure number="6">
+				<barline location="left"><ending number="2" type="start"/></barline>
+				<barline location="right"><ending number="2" type="stop"/></barline>
+			</measure>
+			<measure number="7"/>
+			"#,
+		);
+		assert_eq!(
+			playback_order_of(&musicxml),
+			vec![0, 1, 0, 2, 3, 4, 3, 5, 6]
+		);
+	}
+
+	#[test]
+	fn repeats_without_a_forward_repeat_start_after_the_last_ending() {
+		let musicxml = score(
+			r#"
+			<measure number="1"/>
+			<measure number="2">
+				<barline location="left"><repeat direction="forward"/></barline>
+			</measure>
+			<measure number="3">
+				<barline location="left"><ending number="1" type="start"/></barline>
+				<barline location="right">
+					<ending number="1" type="stop"/>
+					<repeat direction="backward"/>
+				</barline>
+			</measure>
+			<measure number="4">
+				<barline location="left"><ending number="2" type="start"/></barline>
+				<barline location="right"><ending number="2" type="discontinue"/></barline>
+			</measure>
+			<measure number="5"/>
+			<measure number="6">
+				<barline location="right"><repeat direction="backward"/></barline>
+			</measure>
+			<measure number="7"/>
+			"#,
+		);
+		assert_eq!(
+			playback_order_of(&musicxml),
+			vec![0, 1, 2, 1, 3, 4, 5, 4, 5, 6]
+		);
+	}
+
+	fn playback_order_of(musicxml: &str) -> Vec<usize> {
+		let document = Document::parse(musicxml).unwrap();
+		let part = children(document.root_element(), "part").next().unwrap();
+		let measures = children(part, "measure").collect::<Vec<_>>();
+		playback_order(&measures)
+	}
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use crate::pulse::PulseSettings;
+
+/// A single panel placed on the canvas grid.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PanelLayout {
+	/// The name of the widget to show in the panel.
+	#[serde(rename = "type")]
+	pub widget_type: String,
+	pub x: f32,
+	pub y: f32,
+	/// The width of the panel in grid cells. Defaults to the
+	/// widget's preferred size if not specified.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub width: Option<f32>,
+	/// The height of the panel in grid cells. Defaults to the
+	/// widget's preferred size if not specified.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub height: Option<f32>,
+	/// The text shown in the panel's label. Defaults to the
+	/// widget's name if not specified.
+	pub title: Option<String>,
+	#[serde(default)]
+	pub pulse: PulseSettings,
+	/// Any other fields are passed to the widget as its options.
+	#[serde(flatten)]
+	pub options: serde_json::Map<String, serde_json::Value>,
+}
+
+impl PanelLayout {
+	pub fn new(widget_type: impl Into<String>, x: f32, y: f32) -> Self {
+		Self {
+			widget_type: widget_type.into(),
+			x,
+			y,
+			width: None,
+			height: None,
+			title: None,
+			pulse: PulseSettings::default(),
+			options: serde_json::Map::new(),
+		}
+	}
+
+	pub fn title(&self) -> &str {
+		self.title.as_deref().unwrap_or(&self.widget_type)
+	}
+}
+
+/// The layout used by projects that don't specify one.
+pub fn default_layout() -> Vec<PanelLayout> {
+	vec![
+		PanelLayout::new("bpm", 1.0, 1.0),
+		PanelLayout::new("metronome", 1.0, 7.0),
+		PanelLayout::new("key", 1.0, 14.0),
+		PanelLayout::new("chord", 1.0, 20.0),
+	]
+}
@@ -15,6 +15,8 @@ pub struct LiveState {
 	pub audio_manager: AudioManager,
 	pub playing_sound: Option<StaticSoundHandle>,
 	pub time_elapsed: Duration,
+	/// The number of frames drawn since the project was loaded.
+	pub current_frame: u32,
 }
 
 impl LiveState {
@@ -29,6 +31,7 @@ impl LiveState {
 				.context("error creating audio manager")?,
 			playing_sound: None,
 			time_elapsed: Duration::ZERO,
+			current_frame: 0,
 		})
 	}
 
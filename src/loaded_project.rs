@@ -5,7 +5,18 @@ use kira::sound::static_sound::{StaticSoundData, StaticSoundSettings};
 use micro::Context;
 
 use crate::{
-	loaded_shader::LoadedShader, project::Project, shader_param::ShaderParam, track_info::TrackInfo,
+	fonts::Fonts,
+	loaded_shader::{AudioChannel, ShaderFrame},
+	loudness::LoudnessAnalysis,
+	lyrics::Lyrics,
+	post_processing::PostProcessing,
+	project::Project,
+	shader_param::ShaderParam,
+	shader_pipeline::ShaderPipeline,
+	theme::Theme,
+	track_info::TrackInfo,
+	user_track_info::UserTrackInfo,
+	widgets::{Panel, WidgetRegistry},
 };
 
 pub struct LoadedProject {
@@ -13,9 +24,17 @@ pub struct LoadedProject {
 	pub project_path: PathBuf,
 	pub sound_data: StaticSoundData,
 	pub audio_path: PathBuf,
-	pub shader: Option<LoadedShader>,
+	pub loudness: LoudnessAnalysis,
+	pub shader: Option<ShaderPipeline>,
+	/// The audio input shared by every shader pass that reads it.
+	pub audio_channel: AudioChannel,
 	pub shader_params: Vec<ShaderParam>,
+	pub post_processing: PostProcessing,
+	pub lyrics: Option<Lyrics>,
 	pub track_info: TrackInfo,
+	pub theme: Theme,
+	pub fonts: Fonts,
+	pub panels: Vec<Panel>,
 }
 
 impl LoadedProject {
@@ -26,25 +45,95 @@ impl LoadedProject {
 			.parent()
 			.unwrap()
 			.join(&project.audio_file_path);
+		let sound_data = StaticSoundData::from_file(&audio_path, StaticSoundSettings::default())
+			.context("error loading audio")?;
+		let loudness = LoudnessAnalysis::load_or_analyze(&audio_path, &sound_data);
 		let shader = project
 			.shader_path
 			.as_ref()
 			.map(|shader_path| {
-				let shader_full_path = project_path.parent().unwrap().join(shader_path);
-				LoadedShader::load(ctx, shader_full_path).context("error loading shader")
+				ShaderPipeline::load(
+					ctx,
+					shader_path,
+					&project.shader_channels,
+					&project.shader_buffers,
+					project_path.parent().unwrap(),
+				)
+				.context("error loading shader")
 			})
 			.transpose()?;
 		let shader_params = project.shader_params.clone();
+		let post_processing = PostProcessing::load(ctx, &project.post_processing)
+			.context("error loading post-processing effects")?;
+		let lyrics = project
+			.lyrics_path
+			.as_ref()
+			.map(|lyrics_path| {
+				Lyrics::from_file(project_path.parent().unwrap().join(lyrics_path))
+					.context("error loading lyrics")
+			})
+			.transpose()?;
 		let track_info = TrackInfo::new(&project.track_info);
+		let theme = project.theme.theme();
+		let fonts =
+			Fonts::new(ctx, &theme.fonts, project_path.parent()).context("error loading fonts")?;
+		let widget_registry = WidgetRegistry::default();
+		let panels = project
+			.layout
+			.iter()
+			.map(|layout| widget_registry.create_panel(ctx, layout, project_path.parent().unwrap()))
+			.collect::<anyhow::Result<Vec<_>>>()
+			.context("error loading layout")?;
 		Ok(Self {
 			project,
 			project_path: project_path.to_path_buf(),
-			sound_data: StaticSoundData::from_file(&audio_path, StaticSoundSettings::default())
-				.context("error loading audio")?,
+			sound_data,
 			audio_path,
+			loudness,
 			shader,
+			audio_channel: AudioChannel::new(),
 			shader_params,
+			post_processing,
+			lyrics,
 			track_info,
+			theme,
+			fonts,
+			panels,
 		})
 	}
+
+	/// Sends the uniforms for a frame, the music and audio at that point
+	/// in the song and the shader params to every shader pass, if there
+	/// is a shader.
+	pub fn send_shader_uniforms(
+		&mut self,
+		ctx: &Context,
+		frame: ShaderFrame,
+	) -> anyhow::Result<()> {
+		let Some(pipeline) = &mut self.shader else {
+			return Ok(());
+		};
+		// the audio texture is only made once per frame, even if several
+		// passes read it
+		let audio_texture = pipeline
+			.reads_audio()
+			.then(|| self.audio_channel.update(ctx, &self.sound_data, frame.time));
+		for shader in pipeline.passes_mut() {
+			if let Some(audio_texture) = audio_texture {
+				shader.send_audio_channels(audio_texture)?;
+			}
+			shader.send_frame_uniforms(frame)?;
+			shader.send_music_uniforms(&self.track_info, frame.time)?;
+			for param in &self.shader_params {
+				param.send(&shader.shader)?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Replaces the project's track info (without saving the project).
+	pub fn set_user_track_info(&mut self, user_track_info: UserTrackInfo) {
+		self.track_info = TrackInfo::new(&user_track_info);
+		self.project.track_info = user_track_info;
+	}
 }
@@ -1,33 +1,98 @@
+mod audio_channel;
+
 use std::{
+	collections::BTreeMap,
 	path::{Path, PathBuf},
-	time::{Duration, SystemTime},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context as AnyhowContext};
+use glam::{UVec2, Vec2, Vec3, Vec4};
+use micro::{
+	graphics::{
+		shader::Shader,
+		texture::{Texture, TextureSettings},
+	},
+	Context,
 };
 
-use micro::{graphics::shader::Shader, Context};
+use crate::{
+	music_theory::parse_pitch_class, shader_buffer::ShaderBufferName,
+	shader_channel::ShaderChannel, track_info::TrackInfo,
+};
 
-use crate::BASE_RESOLUTION;
+pub use self::audio_channel::AudioChannel;
 
 pub struct LoadedShader {
 	pub shader: Shader,
 	pub path: PathBuf,
 	pub last_modified_time: SystemTime,
 	pub time_since_last_hot_reload: Duration,
+	/// The size of the image the shader draws.
+	pub resolution: UVec2,
+	/// The inputs sent as `iChannel0` to `iChannel3`.
+	pub channels: [Option<LoadedChannel>; 4],
+}
+
+pub enum LoadedChannel {
+	Image(Texture),
+	/// The song's spectrum and waveform, which is sent by the
+	/// [`LoadedProject`](crate::loaded_project::LoadedProject) each frame.
+	Audio,
+	/// A buffer's texture, which is sent by the
+	/// [`ShaderPipeline`](crate::shader_pipeline::ShaderPipeline) each
+	/// frame.
+	Buffer {
+		name: ShaderBufferName,
+		resolution: UVec2,
+	},
 }
 
 impl LoadedShader {
 	const HOT_RELOAD_INTERVAL: Duration = Duration::from_secs(1);
 
-	pub fn load(ctx: &Context, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+	/// Loads a shader and the inputs for its channels. Channel paths are
+	/// relative to `base_dir`, and `buffer_resolutions` has the size of each
+	/// buffer channels can read.
+	pub fn load(
+		ctx: &Context,
+		path: impl AsRef<Path>,
+		resolution: UVec2,
+		channels: &[Option<ShaderChannel>; 4],
+		base_dir: &Path,
+		buffer_resolutions: &BTreeMap<ShaderBufferName, UVec2>,
+	) -> anyhow::Result<Self> {
 		let path = path.as_ref();
 		let shader = Shader::from_fragment_file(ctx, path)?;
-		shader.send_vec2("iResolution", BASE_RESOLUTION.as_vec2())?;
 		let last_modified_time = std::fs::metadata(path)?.modified()?;
-		Ok(Self {
+		let mut loaded_channels: [Option<LoadedChannel>; 4] = Default::default();
+		for (i, (channel, loaded_channel)) in channels.iter().zip(&mut loaded_channels).enumerate()
+		{
+			*loaded_channel = match channel {
+				Some(ShaderChannel::Image { path }) => Some(LoadedChannel::Image(
+					Texture::from_file(ctx, base_dir.join(path), TextureSettings::default())
+						.with_context(|| format!("error loading image for iChannel{}", i))?,
+				)),
+				Some(ShaderChannel::Audio) => Some(LoadedChannel::Audio),
+				Some(ShaderChannel::Buffer { buffer }) => Some(LoadedChannel::Buffer {
+					name: *buffer,
+					resolution: *buffer_resolutions.get(buffer).ok_or_else(|| {
+						anyhow!("iChannel{} reads {}, which isn't defined", i, buffer)
+					})?,
+				}),
+				None => None,
+			};
+		}
+		let loaded_shader = Self {
 			shader,
 			path: path.to_path_buf(),
 			last_modified_time,
 			time_since_last_hot_reload: Duration::ZERO,
-		})
+			resolution,
+			channels: loaded_channels,
+		};
+		loaded_shader.send_static_uniforms()?;
+		Ok(loaded_shader)
 	}
 
 	pub fn update_hot_reload(&mut self, ctx: &Context, delta_time: Duration) -> anyhow::Result<()> {
@@ -36,11 +101,165 @@ impl LoadedShader {
 			let last_modified_time = std::fs::metadata(&self.path)?.modified()?;
 			if last_modified_time > self.last_modified_time {
 				self.shader = Shader::from_fragment_file(ctx, &self.path)?;
-				self.shader
-					.send_vec2("iResolution", BASE_RESOLUTION.as_vec2())?;
+				self.send_static_uniforms()?;
 			}
 			self.time_since_last_hot_reload -= delta_time;
 		}
 		Ok(())
 	}
+
+	/// Returns whether any of the shader's channels read the audio.
+	pub fn reads_audio(&self) -> bool {
+		self.channels
+			.iter()
+			.any(|channel| matches!(channel, Some(LoadedChannel::Audio)))
+	}
+
+	/// Sends the audio texture to each audio channel.
+	pub fn send_audio_channels(&self, texture: &Texture) -> anyhow::Result<()> {
+		for (i, channel) in self.channels.iter().enumerate() {
+			if let Some(LoadedChannel::Audio) = channel {
+				self.shader
+					.send_texture(&format!("iChannel{}", i), texture)?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Sends the Shadertoy uniforms that change every frame.
+	pub fn send_frame_uniforms(&self, frame: ShaderFrame) -> anyhow::Result<()> {
+		self.shader.send_f32("iTime", frame.time.as_secs_f32())?;
+		self.shader
+			.send_f32("iTimeDelta", frame.delta_time.as_secs_f32())?;
+		self.shader.send_i32("iFrame", frame.frame as i32)?;
+		self.shader.send_f32(
+			"iFrameRate",
+			1.0 / frame.delta_time.as_secs_f32().max(f32::EPSILON),
+		)?;
+		self.shader.send_vec4("iDate", date_uniform(frame.date))?;
+		Ok(())
+	}
+
+	/// Sends uniforms describing the music playing at a point in the song:
+	///
+	/// - `iBpm`: the current tempo
+	/// - `iBeatPhase`, `iBarPhase`: how far into the current beat and
+	///   bar we are (from 0 to 1)
+	/// - `iBeat`, `iBar`: the index of the current beat and bar
+	/// - `iTimeSinceChange`: the seconds since the last change in the
+	///   track info
+	/// - `iKeyTonic`, `iChordRoot`: the pitch classes (where 0 is C) of the
+	///   current key and chord, or -1 if there isn't one
+	/// - `iTimeSignature`: the top and bottom of the time signature, or
+	///   zero if there isn't one
+	pub fn send_music_uniforms(
+		&self,
+		track_info: &TrackInfo,
+		time: Duration,
+	) -> anyhow::Result<()> {
+		let state = track_info.music_state(time);
+		let music_state = &state.music_state;
+		let beat_info = track_info.beat_info(time);
+		let beat_index = track_info.beat_index(time);
+		let beats_per_bar = music_state
+			.time_signature
+			.map_or(1, |time_signature| time_signature.top.max(1));
+		let bar_phase =
+			(beat_info.beat_in_bar.unwrap_or(0) as f64 + beat_info.phase) / beats_per_bar as f64;
+		let pitch_class = |name: &Option<String>| {
+			name.as_deref()
+				.and_then(parse_pitch_class)
+				.map_or(-1, |(pitch_class, _)| pitch_class as i32)
+		};
+		self.shader.send_f32("iBpm", music_state.bpm as f32)?;
+		self.shader.send_f32("iBeatPhase", beat_info.phase as f32)?;
+		self.shader.send_f32("iBarPhase", bar_phase as f32)?;
+		self.shader.send_i32("iBeat", beat_index.beat as i32)?;
+		self.shader.send_i32("iBar", beat_index.bar as i32)?;
+		self.shader
+			.send_f32("iTimeSinceChange", (time - state.timestamp).as_secs_f32())?;
+		self.shader
+			.send_i32("iKeyTonic", pitch_class(&music_state.key))?;
+		self.shader
+			.send_i32("iChordRoot", pitch_class(&music_state.chord))?;
+		self.shader.send_vec2(
+			"iTimeSignature",
+			music_state
+				.time_signature
+				.map_or(Vec2::ZERO, |time_signature| {
+					Vec2::new(time_signature.top as f32, time_signature.bottom as f32)
+				}),
+		)?;
+		Ok(())
+	}
+
+	/// Sends the Shadertoy uniforms that only change when the shader
+	/// is (re)loaded.
+	fn send_static_uniforms(&self) -> anyhow::Result<()> {
+		self.shader
+			.send_vec3("iResolution", self.resolution.as_vec2().extend(1.0))?;
+		for (i, channel) in self.channels.iter().enumerate() {
+			let resolution = match channel {
+				Some(LoadedChannel::Image(texture)) => {
+					self.shader
+						.send_texture(&format!("iChannel{}", i), texture)?;
+					texture.size().as_vec2().extend(1.0)
+				}
+				Some(LoadedChannel::Audio) => AudioChannel::SIZE.as_vec2().extend(1.0),
+				Some(LoadedChannel::Buffer { resolution, .. }) => resolution.as_vec2().extend(1.0),
+				None => Vec3::ZERO,
+			};
+			self.shader
+				.send_vec3(&format!("iChannelResolution[{}]", i), resolution)?;
+		}
+		Ok(())
+	}
+}
+
+/// The timing of a frame, which live playback and rendering both
+/// describe the same way so shaders look the same in each.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShaderFrame {
+	/// The position in the song.
+	pub time: Duration,
+	/// The time since the last frame.
+	pub delta_time: Duration,
+	/// The number of frames drawn before this one.
+	pub frame: u32,
+	/// The wall clock time of the frame.
+	pub date: SystemTime,
+}
+
+/// Returns the year, month (from 0), day of the month and seconds since
+/// midnight of a time, like Shadertoy's `iDate`. Uses UTC since the
+/// standard library doesn't know the local time zone.
+fn date_uniform(date: SystemTime) -> Vec4 {
+	let seconds = date
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_secs_f64();
+	let days = (seconds / 86400.0).floor() as i64;
+	let seconds_since_midnight = seconds - days as f64 * 86400.0;
+	// converts days since 1970-01-01 to a date. from
+	// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+	let days = days + 719468;
+	let era = days.div_euclid(146097);
+	let day_of_era = days.rem_euclid(146097);
+	let year_of_era =
+		(day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let shifted_month = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+	let month = if shifted_month < 10 {
+		shifted_month + 2
+	} else {
+		shifted_month - 10
+	};
+	let year = year_of_era + era * 400 + i64::from(month <= 1);
+	Vec4::new(
+		year as f32,
+		month as f32,
+		day as f32,
+		seconds_since_midnight as f32,
+	)
 }
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use glam::UVec2;
+use image::{Rgba, RgbaImage};
+use kira::sound::static_sound::StaticSoundData;
+use micro::{
+	graphics::texture::{Texture, TextureSettings},
+	Context,
+};
+
+use crate::{spectrum::SpectrumAnalyzer, EXPORT_FPS};
+
+/// The number of spectrum bins and waveform samples in the texture.
+const WIDTH: u32 = 512;
+/// Large enough that the first [`WIDTH`] bins cover up to about 11kHz
+/// like on Shadertoy.
+const FFT_SIZE: usize = 2048;
+/// The loudness (in dB) of spectrum bins shown as 0 and 1. Shadertoy
+/// uses -100 to -30 dB from the browser's analyzer, which reads about
+/// 13 dB quieter than [`SpectrumAnalyzer`].
+const MIN_DB: f32 = -87.0;
+const MAX_DB: f32 = -17.0;
+/// How much of the previous frame's spectrum is kept, like the
+/// browser analyzer's smoothing.
+const SMOOTHING: f32 = 0.8;
+/// Previous spectrums weighted less than this are ignored.
+const MIN_SMOOTHING_WEIGHT: f32 = 0.01;
+
+/// A texture like Shadertoy's audio input: the first row holds the
+/// spectrum and the second row holds the waveform, both from 0 to 1.
+///
+/// The texture only depends on the audio data and the timestamp, so
+/// live playback and rendering show the same thing.
+pub struct AudioChannel {
+	analyzer: SpectrumAnalyzer,
+	image: RgbaImage,
+	/// Created the first time the channel is updated and reused after that.
+	texture: Option<Texture>,
+}
+
+impl AudioChannel {
+	pub const SIZE: UVec2 = UVec2::new(WIDTH, 2);
+
+	pub fn new() -> Self {
+		Self {
+			analyzer: SpectrumAnalyzer::new(FFT_SIZE),
+			image: RgbaImage::new(Self::SIZE.x, Self::SIZE.y),
+			texture: None,
+		}
+	}
+
+	/// Updates the texture for the given point in the song.
+	pub fn update(
+		&mut self,
+		ctx: &Context,
+		sound_data: &StaticSoundData,
+		timestamp: Duration,
+	) -> &Texture {
+		for (x, level) in self.spectrum(sound_data, timestamp).into_iter().enumerate() {
+			self.image.put_pixel(x as u32, 0, to_pixel(level));
+		}
+		let end_frame = (timestamp.as_secs_f64() * sound_data.sample_rate as f64) as isize;
+		for x in 0..WIDTH {
+			let frame_index = end_frame - WIDTH as isize + x as isize;
+			let value = usize::try_from(frame_index)
+				.ok()
+				.and_then(|frame_index| sound_data.frames.get(frame_index))
+				.map_or(0.0, |frame| (frame.left + frame.right) / 2.0);
+			self.image.put_pixel(x, 1, to_pixel(value * 0.5 + 0.5));
+		}
+		if let Some(texture) = &self.texture {
+			texture.replace(ctx, 0, 0, &self.image);
+		}
+		self.texture.get_or_insert_with(|| {
+			Texture::from_image(ctx, &self.image, TextureSettings::default())
+		})
+	}
+
+	/// Returns the level (from 0 to 1) of each spectrum bin. Instead of
+	/// remembering previous frames, smoothing looks back at the spectrum
+	/// at earlier timestamps.
+	fn spectrum(&mut self, sound_data: &StaticSoundData, timestamp: Duration) -> Vec<f32> {
+		let num_steps = (MIN_SMOOTHING_WEIGHT.ln() / SMOOTHING.ln()).ceil() as usize;
+		let step = Duration::from_secs_f64(1.0 / EXPORT_FPS);
+		let mut magnitudes = vec![0.0; WIDTH as usize];
+		for i in 0..num_steps {
+			let Some(timestamp) = timestamp.checked_sub(step * i as u32) else {
+				break;
+			};
+			let weight = (1.0 - SMOOTHING) * SMOOTHING.powi(i as i32);
+			let step_magnitudes = self.analyzer.magnitudes(sound_data, timestamp);
+			for (magnitude, step_magnitude) in magnitudes.iter_mut().zip(step_magnitudes) {
+				*magnitude += step_magnitude * weight;
+			}
+		}
+		magnitudes
+			.into_iter()
+			.map(|magnitude| {
+				if magnitude <= 0.0 {
+					return 0.0;
+				}
+				let db = 20.0 * magnitude.log10();
+				((db - MIN_DB) / (MAX_DB - MIN_DB)).clamp(0.0, 1.0)
+			})
+			.collect()
+	}
+}
+
+fn to_pixel(value: f32) -> Rgba<u8> {
+	let value = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+	Rgba([value, value, value, 255])
+}
@@ -0,0 +1,257 @@
+use std::{
+	f64::consts::PI,
+	fs::File,
+	io::{BufReader, BufWriter},
+	path::{Path, PathBuf},
+	time::{Duration, UNIX_EPOCH},
+};
+
+use kira::sound::static_sound::StaticSoundData;
+use serde::{Deserialize, Serialize};
+
+/// The length of each block of the analysis in seconds.
+const BLOCK_DURATION: f64 = 0.1;
+/// The number of blocks RMS levels are averaged over (300ms).
+const RMS_WINDOW_BLOCKS: usize = 3;
+/// The number of blocks short-term loudness is measured over (3s, as
+/// specified by EBU R128).
+const SHORT_TERM_WINDOW_BLOCKS: usize = 30;
+/// The level used for silence, since JSON can't store negative infinity.
+pub const SILENCE_DB: f32 = -120.0;
+/// Bumped whenever the analysis changes so old cache files are ignored.
+const CACHE_VERSION: u32 = 1;
+
+/// Levels measured over the whole song, calculated ahead of time so
+/// meters can look them up by timestamp.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoudnessAnalysis {
+	pub blocks: Vec<LoudnessBlock>,
+}
+
+/// The levels at the end of a 100ms block of audio.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoudnessBlock {
+	/// The highest sample level in the block, in dBFS.
+	pub peak: f32,
+	/// The RMS level over the last 300ms, in dBFS.
+	pub rms: f32,
+	/// The K-weighted loudness over the last 3 seconds, in LUFS.
+	pub short_term_lufs: f32,
+}
+
+impl LoudnessAnalysis {
+	/// Loads the analysis for an audio file from the cache file next to
+	/// it, or analyzes the audio and writes a new cache file if there
+	/// isn't an up to date one.
+	pub fn load_or_analyze(audio_path: &Path, sound_data: &StaticSoundData) -> Self {
+		let cache_path = cache_path(audio_path);
+		let source = AnalysisSource::new(audio_path, sound_data);
+		if let Some(analysis) = read_cache(&cache_path, &source) {
+			return analysis;
+		}
+		let analysis = Self::analyze(sound_data);
+		// the cache only saves time, so it's fine if we can't write it
+		// (for example, if the audio is in a read-only folder)
+		let _ = write_cache(&cache_path, &source, &analysis);
+		analysis
+	}
+
+	pub fn analyze(sound_data: &StaticSoundData) -> Self {
+		let sample_rate = sound_data.sample_rate as f64;
+		let frames_per_block = ((sample_rate * BLOCK_DURATION) as usize).max(1);
+		let mut filters = [
+			KWeightingFilter::new(sample_rate),
+			KWeightingFilter::new(sample_rate),
+		];
+		// per block: (peak, sum of squares, sum of k-weighted squares)
+		let block_sums = sound_data
+			.frames
+			.chunks(frames_per_block)
+			.map(|frames| {
+				let mut peak = 0.0f32;
+				let mut sum_of_squares = 0.0;
+				let mut weighted_sum_of_squares = 0.0;
+				for frame in frames {
+					peak = peak.max(frame.left.abs()).max(frame.right.abs());
+					for (channel, sample) in [frame.left, frame.right].into_iter().enumerate() {
+						let sample = sample as f64;
+						sum_of_squares += sample * sample;
+						let weighted = filters[channel].process(sample);
+						weighted_sum_of_squares += weighted * weighted;
+					}
+				}
+				(peak, sum_of_squares, weighted_sum_of_squares)
+			})
+			.collect::<Vec<_>>();
+		let blocks = (0..block_sums.len())
+			.map(|i| {
+				let window_mean = |num_blocks: usize, value: fn(&(f32, f64, f64)) -> f64| {
+					let start = (i + 1).saturating_sub(num_blocks);
+					let sum: f64 = block_sums[start..=i].iter().map(value).sum();
+					// the window is always the full length so the level
+					// ramps up at the start of the song like a real meter
+					sum / (num_blocks * frames_per_block) as f64
+				};
+				let rms_mean_square = window_mean(RMS_WINDOW_BLOCKS, |sums| sums.1) / 2.0;
+				let short_term_mean_square = window_mean(SHORT_TERM_WINDOW_BLOCKS, |sums| sums.2);
+				LoudnessBlock {
+					peak: to_db(20.0 * (block_sums[i].0 as f64).log10()),
+					rms: to_db(10.0 * rms_mean_square.log10()),
+					short_term_lufs: to_db(-0.691 + 10.0 * short_term_mean_square.log10()),
+				}
+			})
+			.collect();
+		Self { blocks }
+	}
+
+	/// Returns the levels for the block containing the given time.
+	pub fn at(&self, timestamp: Duration) -> LoudnessBlock {
+		let index = (timestamp.as_secs_f64() / BLOCK_DURATION) as usize;
+		self.blocks
+			.get(index)
+			.or(self.blocks.last())
+			.copied()
+			.unwrap_or(LoudnessBlock {
+				peak: SILENCE_DB,
+				rms: SILENCE_DB,
+				short_term_lufs: SILENCE_DB,
+			})
+	}
+}
+
+/// Information about the audio an analysis was made from, used to tell
+/// whether a cache file is out of date.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct AnalysisSource {
+	version: u32,
+	file_size: Option<u64>,
+	modified: Option<u64>,
+	sample_rate: u32,
+	num_frames: usize,
+}
+
+impl AnalysisSource {
+	fn new(audio_path: &Path, sound_data: &StaticSoundData) -> Self {
+		let metadata = std::fs::metadata(audio_path).ok();
+		Self {
+			version: CACHE_VERSION,
+			file_size: metadata.as_ref().map(|metadata| metadata.len()),
+			modified: metadata
+				.and_then(|metadata| metadata.modified().ok())
+				.and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+				.map(|modified| modified.as_secs()),
+			sample_rate: sound_data.sample_rate,
+			num_frames: sound_data.frames.len(),
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+	source: AnalysisSource,
+	analysis: LoudnessAnalysis,
+}
+
+fn cache_path(audio_path: &Path) -> PathBuf {
+	let mut file_name = audio_path.file_name().unwrap_or_default().to_os_string();
+	file_name.push(".loudness.json");
+	audio_path.with_file_name(file_name)
+}
+
+fn read_cache(cache_path: &Path, source: &AnalysisSource) -> Option<LoudnessAnalysis> {
+	let file = File::open(cache_path).ok()?;
+	let cache: CacheFile = serde_json::from_reader(BufReader::new(file)).ok()?;
+	(cache.source == *source).then_some(cache.analysis)
+}
+
+fn write_cache(
+	cache_path: &Path,
+	source: &AnalysisSource,
+	analysis: &LoudnessAnalysis,
+) -> anyhow::Result<()> {
+	let file = File::create(cache_path)?;
+	serde_json::to_writer(
+		BufWriter::new(file),
+		&CacheFile {
+			source: source.clone(),
+			analysis: analysis.clone(),
+		},
+	)?;
+	Ok(())
+}
+
+fn to_db(db: f64) -> f32 {
+	if db.is_finite() {
+		(db as f32).max(SILENCE_DB)
+	} else {
+		SILENCE_DB
+	}
+}
+
+/// The two-stage filter ITU-R BS.1770 applies before measuring loudness:
+/// a high shelf that models the head, followed by a high pass.
+struct KWeightingFilter {
+	shelf: Biquad,
+	high_pass: Biquad,
+}
+
+impl KWeightingFilter {
+	fn new(sample_rate: f64) -> Self {
+		let shelf = {
+			let frequency = 1681.974450955533;
+			let gain = 3.999843853973347;
+			let q = 0.7071752369554196;
+			let k = (PI * frequency / sample_rate).tan();
+			let vh = 10.0f64.powf(gain / 20.0);
+			let vb = vh.powf(0.4996667741545416);
+			let a0 = 1.0 + k / q + k * k;
+			Biquad::new(
+				[
+					(vh + vb * k / q + k * k) / a0,
+					2.0 * (k * k - vh) / a0,
+					(vh - vb * k / q + k * k) / a0,
+				],
+				[2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+			)
+		};
+		let high_pass = {
+			let frequency = 38.13547087602444;
+			let q = 0.5003270373238773;
+			let k = (PI * frequency / sample_rate).tan();
+			let a0 = 1.0 + k / q + k * k;
+			Biquad::new(
+				[1.0, -2.0, 1.0],
+				[2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+			)
+		};
+		Self { shelf, high_pass }
+	}
+
+	fn process(&mut self, sample: f64) -> f64 {
+		self.high_pass.process(self.shelf.process(sample))
+	}
+}
+
+/// A second-order IIR filter (transposed direct form II).
+struct Biquad {
+	b: [f64; 3],
+	a: [f64; 2],
+	state: [f64; 2],
+}
+
+impl Biquad {
+	fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+		Self {
+			b,
+			a,
+			state: [0.0; 2],
+		}
+	}
+
+	fn process(&mut self, input: f64) -> f64 {
+		let output = self.b[0] * input + self.state[0];
+		self.state[0] = self.b[1] * input - self.a[0] * output + self.state[1];
+		self.state[1] = self.b[2] * input - self.a[1] * output;
+		output
+	}
+}
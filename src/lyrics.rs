@@ -0,0 +1,251 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::Context;
+
+/// Timed lyrics for a song, imported from an LRC file.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Lyrics {
+	/// The lines of the song, sorted by start time.
+	pub lines: Vec<LyricLine>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricLine {
+	pub start: Duration,
+	pub text: String,
+	/// Per-word timing from an enhanced LRC file. Empty if the file
+	/// only has line timestamps.
+	pub words: Vec<LyricWord>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricWord {
+	pub start: Duration,
+	/// The byte offset in [`LyricLine::text`] where the word ends.
+	pub end_index: usize,
+}
+
+impl Lyrics {
+	pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+		let lrc = std::fs::read_to_string(path)?;
+		Self::from_lrc(&lrc)
+	}
+
+	/// Parses an LRC or enhanced LRC file.
+	///
+	/// Lines can have multiple `[mm:ss.xx]` timestamps if they're
+	/// repeated, and words can be timed with `<mm:ss.xx>` tags. The
+	/// `[offset:]` tag is applied to every timestamp; other metadata
+	/// tags are ignored.
+	pub fn from_lrc(lrc: &str) -> anyhow::Result<Self> {
+		let mut offset_ms = 0i64;
+		let mut timed_lines = vec![];
+		for (line_index, line) in lrc.lines().enumerate() {
+			let mut rest = line.trim();
+			let mut line_timestamps = vec![];
+			while let Some(tag_end) = rest.strip_prefix('[').and_then(|tag| tag.find(']')) {
+				let tag = &rest[1..tag_end + 1];
+				rest = &rest[tag_end + 2..];
+				if let Some(timestamp) = parse_timestamp(tag) {
+					line_timestamps.push(timestamp);
+				} else if let Some(offset) = tag.strip_prefix("offset:") {
+					offset_ms = offset
+						.trim()
+						.parse()
+						.with_context(|| format!("invalid offset on line {}", line_index + 1))?;
+				}
+			}
+			if line_timestamps.is_empty() {
+				continue;
+			}
+			let (text, words) = parse_words(rest);
+			// word timestamps are written for the first time the line is
+			// sung, so shift them for repeats
+			let first_timestamp = line_timestamps[0];
+			for timestamp in line_timestamps {
+				let words = words
+					.iter()
+					.map(|&(start, end_index)| {
+						let start = start.saturating_add(timestamp - first_timestamp);
+						(start, end_index)
+					})
+					.collect::<Vec<_>>();
+				timed_lines.push((timestamp, text.clone(), words));
+			}
+		}
+		// a positive offset makes the lyrics appear sooner
+		let apply_offset = |timestamp_ms: i64| {
+			Duration::from_millis(timestamp_ms.saturating_sub(offset_ms).max(0) as u64)
+		};
+		let mut lines = timed_lines
+			.into_iter()
+			.map(|(start, text, words)| LyricLine {
+				start: apply_offset(start),
+				text,
+				words: words
+					.into_iter()
+					.map(|(start, end_index)| LyricWord {
+						start: apply_offset(start),
+						end_index,
+					})
+					.collect(),
+			})
+			.collect::<Vec<_>>();
+		lines.sort_by_key(|line| line.start);
+		Ok(Self { lines })
+	}
+
+	/// Returns the index of the line being sung at the given time,
+	/// or `None` if the first line hasn't started yet.
+	pub fn line_index(&self, timestamp: Duration) -> Option<usize> {
+		self.lines.iter().rposition(|line| line.start <= timestamp)
+	}
+}
+
+impl LyricLine {
+	/// Returns the part of the line that has been sung by the given time.
+	pub fn sung_text(&self, timestamp: Duration) -> &str {
+		let end_index = self
+			.words
+			.iter()
+			.rev()
+			.find(|word| word.start <= timestamp)
+			.map_or(0, |word| word.end_index);
+		&self.text[..end_index]
+	}
+}
+
+/// Parses the text of a line, returning the text without word timestamps
+/// and the start time (in milliseconds) and end position of each word.
+fn parse_words(line: &str) -> (String, Vec<(i64, usize)>) {
+	let mut untrimmed_text = String::new();
+	// the start time of each word and where it starts in the text
+	let mut word_starts = vec![];
+	let mut rest = line;
+	while let Some(tag_start) = rest.find('<') {
+		untrimmed_text.push_str(&rest[..tag_start]);
+		let timestamp = rest[tag_start..].find('>').and_then(|tag_length| {
+			let tag = &rest[tag_start + 1..tag_start + tag_length];
+			parse_timestamp(tag).map(|timestamp| (timestamp, tag_length))
+		});
+		match timestamp {
+			Some((timestamp, tag_length)) => {
+				word_starts.push((timestamp, untrimmed_text.len()));
+				rest = &rest[tag_start + tag_length + 1..];
+			}
+			// a "<" that doesn't start a word timestamp is part of the
+			// lyrics, like in "I <3 you"
+			None => {
+				untrimmed_text.push('<');
+				rest = &rest[tag_start + 1..];
+			}
+		}
+	}
+	untrimmed_text.push_str(rest);
+	let leading_whitespace = untrimmed_text.len() - untrimmed_text.trim_start().len();
+	let text = untrimmed_text.trim().to_string();
+	let to_text_index = |index: usize| index.saturating_sub(leading_whitespace).min(text.len());
+	let words = word_starts
+		.iter()
+		.enumerate()
+		.filter_map(|(i, &(start_time, start_index))| {
+			let end_index = word_starts
+				.get(i + 1)
+				.map_or(untrimmed_text.len(), |next_word| next_word.1);
+			let start_index = to_text_index(start_index);
+			let word = &text[start_index..to_text_index(end_index)];
+			// a timestamp with nothing after it just marks the end
+			// of the previous word
+			(!word.trim().is_empty()).then_some((start_time, start_index + word.trim_end().len()))
+		})
+		.collect();
+	(text, words)
+}
+
+/// Parses an `mm:ss.xx` timestamp into milliseconds. Returns `None` for
+/// negative, non-finite or out of range times.
+fn parse_timestamp(timestamp: &str) -> Option<i64> {
+	let (minutes, seconds) = timestamp.trim().split_once(':')?;
+	let minutes: i64 = minutes.parse().ok()?;
+	let seconds: f64 = seconds.parse().ok()?;
+	let seconds_ms = (seconds * 1000.0).round();
+	if minutes < 0 || !(0.0..i64::MAX as f64).contains(&seconds_ms) {
+		return None;
+	}
+	minutes.checked_mul(60_000)?.checked_add(seconds_ms as i64)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_timestamps() {
+		assert_eq!(parse_timestamp("01:02.50"), Some(62_500));
+		assert_eq!(parse_timestamp(" 0:05 "), Some(5_000));
+		for timestamp in [
+			"00:inf",
+			"00:nan",
+			"00:-1",
+			"-1:00",
+			"00:1e300",
+			"9999999999999999:00",
+			"ti:Title",
+			"00.50",
+		] {
+			assert_eq!(parse_timestamp(timestamp), None, "{}", timestamp);
+		}
+	}
+
+	#[test]
+	fn parses_lrc() {
+		let lyrics = Lyrics::from_lrc(
+			"[ti:Song]
+[offset:500]
+[00:12.00][00:42.00]I <3 you
+[00:20.00]<00:20.00> Hello <00:21.00>world <00:22.00>
+[00:inf]not a line
+",
+		)
+		.unwrap();
+		let starts = lyrics
+			.lines
+			.iter()
+			.map(|line| line.start)
+			.collect::<Vec<_>>();
+		assert_eq!(
+			starts,
+			vec![
+				Duration::from_millis(11_500),
+				Duration::from_millis(19_500),
+				Duration::from_millis(41_500),
+			]
+		);
+		assert_eq!(lyrics.lines[0].text, "I <3 you");
+		assert!(lyrics.lines[0].words.is_empty());
+		let line = &lyrics.lines[1];
+		assert_eq!(line.text, "Hello world");
+		assert_eq!(
+			line.words,
+			vec![
+				LyricWord {
+					start: Duration::from_millis(19_500),
+					end_index: 5,
+				},
+				LyricWord {
+					start: Duration::from_millis(20_500),
+					end_index: 11,
+				},
+			]
+		);
+		assert_eq!(line.sung_text(Duration::from_secs(20)), "Hello");
+	}
+
+	#[test]
+	fn doesnt_overflow_on_extreme_offsets() {
+		for offset in [i64::MIN, i64::MAX] {
+			let lrc = format!("[offset:{}]\n[00:01.00]Line", offset);
+			assert_eq!(Lyrics::from_lrc(&lrc).unwrap().lines.len(), 1);
+		}
+	}
+}
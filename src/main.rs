@@ -1,50 +1,68 @@
+mod analysis;
+mod benchmark;
+mod fonts;
 mod format;
+mod interchange;
+mod layout;
 mod live_state;
 mod loaded_project;
 mod loaded_shader;
+mod loudness;
+mod lyrics;
 mod music_state;
 mod music_theory;
+mod post_effect;
+mod post_processing;
 mod project;
+mod pulse;
 mod rendering_state;
+mod shader_buffer;
+mod shader_channel;
 mod shader_param;
+mod shader_pipeline;
+mod spectrum;
+mod theme;
 mod track_info;
+mod transition;
 mod ui;
 mod user_track_info;
 mod widgets;
 
-use std::{io::Write, path::PathBuf, time::Duration};
+use std::{
+	io::Write,
+	path::PathBuf,
+	time::{Duration, SystemTime},
+};
 
+use benchmark::run_benchmark;
 use clap::Parser;
 use glam::{UVec2, Vec2};
 use live_state::LiveState;
 use loaded_project::LoadedProject;
-use loaded_shader::LoadedShader;
+use loaded_shader::ShaderFrame;
 use micro::{
-	graphics::{
-		mesh::Mesh,
-		text::{Font, FontSettings},
-		Canvas, CanvasSettings, DrawParams,
-	},
+	graphics::{mesh::Mesh, Canvas, CanvasSettings, DrawParams},
 	input::Scancode,
 	math::Rect,
 	Context, ContextSettings, Event, State, WindowMode,
 };
-use palette::LinSrgba;
 use rendering_state::RenderingState;
-use shader_param::ShaderParamKind;
-use track_info::TrackInfo;
-use ui::show_dialog_if_error;
-use widgets::{draw_bpm_panel, draw_chord_panel, draw_key_panel, draw_metronome_panel};
+use theme::Theme;
+use ui::{analysis::AnalysisState, import_export::ImportExportSettings, show_dialog_if_error};
+use widgets::FrameContext;
 
 const BASE_RESOLUTION: UVec2 = UVec2::new(3840, 2160);
 const EXPORT_FPS: f64 = 60.0;
-const OFFWHITE: LinSrgba = LinSrgba::new(0.8, 0.8, 0.8, 1.0);
 const FLAT_SYMBOL: &str = "♭";
 const SHARP_SYMBOL: &str = "♯";
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Parser)]
 struct Args {
 	project_path: Option<PathBuf>,
+	/// Render this many frames of the project without showing them, print
+	/// how long they took with and without text caching, and exit.
+	#[arg(long, requires = "project_path")]
+	benchmark: Option<u32>,
 }
 
 fn main() {
@@ -61,86 +79,96 @@ fn main() {
 
 struct MainState {
 	mode: Mode,
-	fonts: Fonts,
 	canvas: Canvas,
 	show_shader_params_editor: bool,
+	show_import_export_window: bool,
+	import_export_settings: ImportExportSettings,
+	show_analysis_window: bool,
+	analysis_state: AnalysisState,
 }
 
 impl MainState {
 	pub fn new(ctx: &mut Context) -> anyhow::Result<Self> {
 		let args = Args::parse();
-		Ok(Self {
-			mode: args
-				.project_path
-				.map(|project_path| -> anyhow::Result<Mode> {
-					Ok(Mode::Live(LiveState::new(ctx, project_path)?))
-				})
-				.transpose()?
-				.unwrap_or_default(),
-			fonts: Fonts {
-				small: Font::from_file(
-					ctx,
-					"resources/iosevka-italic.ttf",
-					FontSettings {
-						scale: 48.0,
-						..Default::default()
-					},
-				)?,
-				medium: Font::from_file(
-					ctx,
-					"resources/iosevka-semibold.ttf",
-					FontSettings {
-						scale: 64.0,
-						..Default::default()
-					},
-				)?,
-				large: Font::from_file(
-					ctx,
-					"resources/iosevka-semibold.ttf",
-					FontSettings {
-						scale: 128.0,
-						..Default::default()
-					},
-				)?,
-				music_large: Font::from_file(
-					ctx,
-					"resources/iosevka-semibold.ttf",
-					FontSettings {
-						scale: 128.0,
-						chars: FLAT_SYMBOL.to_string() + SHARP_SYMBOL,
-						..Default::default()
-					},
-				)?,
-				music_medium: Font::from_file(
-					ctx,
-					"resources/iosevka-semibold.ttf",
-					FontSettings {
-						scale: 64.0,
-						chars: FLAT_SYMBOL.to_string() + SHARP_SYMBOL,
-						..Default::default()
-					},
-				)?,
-			},
+		if let (Some(num_frames), Some(project_path)) = (args.benchmark, &args.project_path) {
+			run_benchmark(ctx, project_path, num_frames)?;
+			ctx.quit();
+			return Ok(Self::with_mode(ctx, Mode::Idle));
+		}
+		let mode = args
+			.project_path
+			.map(|project_path| -> anyhow::Result<Mode> {
+				Ok(Mode::Live(LiveState::new(ctx, project_path)?))
+			})
+			.transpose()?
+			.unwrap_or_default();
+		Ok(Self::with_mode(ctx, mode))
+	}
+
+	fn with_mode(ctx: &Context, mode: Mode) -> Self {
+		Self {
+			mode,
 			canvas: Canvas::new(ctx, BASE_RESOLUTION, CanvasSettings::default()),
 			show_shader_params_editor: false,
-		})
+			show_import_export_window: false,
+			import_export_settings: ImportExportSettings::default(),
+			show_analysis_window: false,
+			analysis_state: AnalysisState::default(),
+		}
+	}
+
+	/// Draws a frame of a project, including the shader buffers and
+	/// post-processing, and returns the canvas with the finished frame.
+	fn draw_frame<'a>(
+		ctx: &mut Context,
+		loaded_project: &'a mut LoadedProject,
+		canvas: &'a Canvas,
+		time_elapsed: Duration,
+	) -> anyhow::Result<&'a Canvas> {
+		if let Some(pipeline) = &mut loaded_project.shader {
+			pipeline.draw_buffers(ctx)?;
+		}
+		canvas.render_to(ctx, |ctx| {
+			Self::draw_canvas_contents(ctx, loaded_project, time_elapsed)
+		})?;
+		loaded_project
+			.post_processing
+			.apply(ctx, canvas, time_elapsed)
 	}
 
 	fn draw_canvas_contents(
 		ctx: &mut Context,
-		shader: &Option<LoadedShader>,
-		fonts: &Fonts,
-		track_info: &TrackInfo,
+		loaded_project: &mut LoadedProject,
 		time_elapsed: Duration,
 	) -> Result<(), anyhow::Error> {
-		if let Some(LoadedShader { shader, .. }) = shader {
+		let LoadedProject {
+			shader,
+			track_info,
+			sound_data,
+			loudness,
+			lyrics,
+			theme,
+			fonts,
+			panels,
+			..
+		} = loaded_project;
+		ctx.clear(theme.background);
+		if let Some(pipeline) = shader {
 			Mesh::rectangle(ctx, Rect::new(Vec2::ZERO, BASE_RESOLUTION.as_vec2()))
-				.draw(ctx, shader);
+				.draw(ctx, &pipeline.image.shader);
+		}
+		let frame = FrameContext::new(
+			time_elapsed,
+			track_info,
+			sound_data,
+			loudness,
+			lyrics.as_ref(),
+			theme,
+			fonts,
+		);
+		for panel in panels {
+			panel.draw(ctx, &frame)?;
 		}
-		draw_bpm_panel(ctx, track_info, time_elapsed, fonts, Vec2::new(1.0, 1.0))?;
-		draw_metronome_panel(ctx, track_info, time_elapsed, fonts, Vec2::new(1.0, 7.0))?;
-		draw_key_panel(ctx, track_info, time_elapsed, fonts, Vec2::new(1.0, 14.0))?;
-		draw_chord_panel(ctx, track_info, time_elapsed, fonts, Vec2::new(1.0, 20.0))?;
 		Ok(())
 	}
 }
@@ -151,6 +179,12 @@ impl State<anyhow::Error> for MainState {
 		if self.show_shader_params_editor {
 			self.render_shader_params_editor(egui_ctx);
 		}
+		if self.show_import_export_window {
+			self.render_import_export_window(egui_ctx);
+		}
+		if self.show_analysis_window {
+			self.render_analysis_window(egui_ctx);
+		}
 		Ok(())
 	}
 
@@ -168,47 +202,41 @@ impl State<anyhow::Error> for MainState {
 	fn update(&mut self, ctx: &mut Context, delta_time: Duration) -> Result<(), anyhow::Error> {
 		match &mut self.mode {
 			Mode::Live(LiveState {
-				loaded_project: LoadedProject {
-					shader,
-					shader_params,
-					..
-				},
+				loaded_project,
 				playing_sound,
 				time_elapsed,
+				current_frame,
 				..
 			}) => {
 				if playing_sound.is_some() {
 					*time_elapsed += delta_time;
 				}
-				if let Some(shader) = shader {
-					show_dialog_if_error(shader.update_hot_reload(ctx, delta_time));
-					shader
-						.shader
-						.send_f32("iTime", time_elapsed.as_secs_f32())?;
-					for param in shader_params {
-						match &param.kind {
-							ShaderParamKind::Float { value, .. } => {
-								shader.shader.send_f32(&param.name, *value)?;
-							}
-							ShaderParamKind::Color { value } => {
-								shader.shader.send_color(&param.name, value.0)?;
-							}
-						}
-					}
+				if let Some(pipeline) = &mut loaded_project.shader {
+					show_dialog_if_error(pipeline.update_hot_reload(ctx, delta_time));
 				}
+				loaded_project.send_shader_uniforms(
+					ctx,
+					ShaderFrame {
+						time: *time_elapsed,
+						delta_time,
+						frame: *current_frame,
+						date: SystemTime::now(),
+					},
+				)?;
+				*current_frame += 1;
 			}
 			Mode::Rendering(RenderingState {
-				loaded_project: LoadedProject {
-					shader: Some(shader),
-					..
-				},
+				loaded_project,
 				current_frame,
+				start_date,
 				..
 			}) => {
-				let time_elapsed = *current_frame * Duration::from_secs_f64(1.0 / EXPORT_FPS);
-				shader
-					.shader
-					.send_f32("iTime", time_elapsed.as_secs_f32())?;
+				RenderingState::send_frame_uniforms(
+					ctx,
+					loaded_project,
+					*current_frame,
+					*start_date,
+				)?;
 			}
 			_ => (),
 		}
@@ -216,44 +244,40 @@ impl State<anyhow::Error> for MainState {
 	}
 
 	fn draw(&mut self, ctx: &mut Context) -> Result<(), anyhow::Error> {
-		self.canvas.render_to(ctx, |ctx| -> anyhow::Result<()> {
-			ctx.clear(OFFWHITE);
-			match &self.mode {
-				Mode::Idle => {}
-				Mode::Live(LiveState {
-					loaded_project: LoadedProject {
-						shader, track_info, ..
-					},
-					time_elapsed,
-					..
-				}) => {
-					Self::draw_canvas_contents(
-						ctx,
-						shader,
-						&self.fonts,
-						track_info,
-						*time_elapsed,
-					)?;
-				}
-				Mode::Rendering(RenderingState {
-					loaded_project: LoadedProject {
-						shader, track_info, ..
-					},
-					current_frame,
-					..
-				}) => {
-					let time_elapsed = *current_frame * Duration::from_secs_f64(1.0 / EXPORT_FPS);
-					Self::draw_canvas_contents(ctx, shader, &self.fonts, track_info, time_elapsed)?;
-				}
+		let output_canvas = match &mut self.mode {
+			Mode::Idle => {
+				self.canvas.render_to(ctx, |ctx| -> anyhow::Result<()> {
+					ctx.clear(Theme::default().background);
+					Ok(())
+				})?;
+				&self.canvas
 			}
-			Ok(())
-		})?;
-		self.canvas.draw(
+			Mode::Live(LiveState {
+				loaded_project,
+				time_elapsed,
+				..
+			}) => Self::draw_frame(ctx, loaded_project, &self.canvas, *time_elapsed)?,
+			Mode::Rendering(RenderingState {
+				loaded_project,
+				current_frame,
+				canvas_read_buffer,
+				..
+			}) => {
+				let output_canvas = Self::draw_frame(
+					ctx,
+					loaded_project,
+					&self.canvas,
+					RenderingState::frame_time(*current_frame),
+				)?;
+				output_canvas.read(canvas_read_buffer);
+				output_canvas
+			}
+		};
+		output_canvas.draw(
 			ctx,
-			DrawParams::new().scaled(ctx.window_size().as_vec2() / self.canvas.size().as_vec2()),
+			DrawParams::new().scaled(ctx.window_size().as_vec2() / output_canvas.size().as_vec2()),
 		);
 		if let Mode::Rendering(rendering_state) = &mut self.mode {
-			self.canvas.read(&mut rendering_state.canvas_read_buffer);
 			let ffmpeg_stdin = rendering_state.ffmpeg_process.stdin.as_mut().unwrap();
 			let write_result = ffmpeg_stdin.write_all(&rendering_state.canvas_read_buffer);
 			if write_result.is_err() {
@@ -278,11 +302,3 @@ enum Mode {
 	Live(LiveState),
 	Rendering(RenderingState),
 }
-
-struct Fonts {
-	small: Font,
-	medium: Font,
-	large: Font,
-	music_large: Font,
-	music_medium: Font,
-}
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::{music_theory::TimeSignature, user_track_info::Change};
@@ -11,6 +13,12 @@ pub struct MusicState {
 	pub time_signature: Option<TimeSignature>,
 	pub key: Option<String>,
 	pub chord: Option<String>,
+	/// The name of the part of the song, like "Verse 1" or "Chorus".
+	#[serde(default)]
+	pub section: Option<String>,
+	/// Whether image widgets with each ID are shown.
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub image_visibility: BTreeMap<String, bool>,
 }
 
 impl MusicState {
@@ -31,6 +39,15 @@ impl MusicState {
 		if let Some(new_chord) = &change.chord {
 			new.chord = new_chord.clone();
 		}
+		if let Some(new_section) = &change.section {
+			new.section = new_section.clone();
+		}
+		new.image_visibility.extend(
+			change
+				.image_visibility
+				.iter()
+				.map(|(id, visible)| (id.clone(), *visible)),
+		);
 		new
 	}
 }
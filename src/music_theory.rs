@@ -1,3 +1,9 @@
+mod chord;
+mod key;
+mod pitch_class;
 mod time_signature;
 
+pub use chord::*;
+pub use key::*;
+pub use pitch_class::*;
 pub use time_signature::*;
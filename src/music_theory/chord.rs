@@ -0,0 +1,21 @@
+/// Chord qualities, roughly from most to least common, as the suffix
+/// used in chord names and the notes in the chord (in semitones above
+/// the root).
+pub const CHORD_QUALITIES: [(&str, &[usize]); 16] = [
+	("", &[0, 4, 7]),
+	("m", &[0, 3, 7]),
+	("7", &[0, 4, 7, 10]),
+	("m7", &[0, 3, 7, 10]),
+	("maj7", &[0, 4, 7, 11]),
+	("dim", &[0, 3, 6]),
+	("sus4", &[0, 5, 7]),
+	("sus2", &[0, 2, 7]),
+	("aug", &[0, 4, 8]),
+	("6", &[0, 4, 7, 9]),
+	("m6", &[0, 3, 7, 9]),
+	("m7b5", &[0, 3, 6, 10]),
+	("dim7", &[0, 3, 6, 9]),
+	("9", &[0, 2, 4, 7, 10]),
+	("add9", &[0, 2, 4, 7]),
+	("5", &[0, 7]),
+];
@@ -0,0 +1,127 @@
+use super::{parse_pitch_class, pitch_class_name};
+
+/// The tonics of the major keys with -7 to 7 sharps, in the order of
+/// the circle of fifths.
+const MAJOR_TONICS: [&str; 15] = [
+	"Cb", "Gb", "Db", "Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E", "B", "F#", "C#",
+];
+/// The tonics of the minor keys with -7 to 7 sharps.
+const MINOR_TONICS: [&str; 15] = [
+	"Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E", "B", "F#", "C#", "G#", "D#", "A#",
+];
+
+/// Returns the name of the key with the given number of sharps (or flats,
+/// if negative), like "G" or "Em". Returns `None` for more than 7.
+pub fn key_name_from_fifths(fifths: i32, minor: bool) -> Option<String> {
+	let index = usize::try_from(fifths + 7).ok()?;
+	if minor {
+		MINOR_TONICS.get(index).map(|tonic| format!("{}m", tonic))
+	} else {
+		MAJOR_TONICS.get(index).map(|tonic| tonic.to_string())
+	}
+}
+
+/// The usual names of the major keys with each tonic, starting from C.
+const MAJOR_KEY_NAMES: [&str; 12] = [
+	"C", "Db", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B",
+];
+/// The usual names of the minor keys with each tonic, starting from C.
+const MINOR_KEY_NAMES: [&str; 12] = [
+	"Cm", "C#m", "Dm", "Ebm", "Em", "Fm", "F#m", "Gm", "G#m", "Am", "Bbm", "Bm",
+];
+
+/// Returns the name of the key with the given tonic (as a pitch class,
+/// where 0 is C), like "Eb" or "F#m".
+pub fn key_name(tonic: usize, minor: bool) -> &'static str {
+	if minor {
+		MINOR_KEY_NAMES[tonic % 12]
+	} else {
+		MAJOR_KEY_NAMES[tonic % 12]
+	}
+}
+
+/// Returns whether notes in a key (like "F" or "Cm") are usually spelled
+/// with flats rather than sharps.
+pub fn key_uses_flats(key: &str) -> bool {
+	let Some((tonic, rest)) = parse_pitch_class(key.trim()) else {
+		return false;
+	};
+	let minor = rest.starts_with('m') && !rest.starts_with("maj");
+	let relative_major = if minor { tonic + 3 } else { tonic };
+	let name = key_name(relative_major, false);
+	name == "F" || name.ends_with('b')
+}
+
+/// Returns the name of a pitch class (where 0 is C) spelled as a scale
+/// degree of a key, so in Eb the pitch class 8 is "Ab" and in E it's
+/// "G#". Chromatic notes are spelled as b2, b3, #4, b6 or b7. Falls
+/// back to the usual sharp or flat spelling if the key can't be read
+/// or the spelling would need a double sharp or flat.
+pub fn spell_pitch_class(pitch_class: usize, key: &str) -> String {
+	/// The number of letters above the tonic for each number of
+	/// semitones above the tonic.
+	const DEGREE_LETTERS: [usize; 12] = [0, 1, 1, 2, 2, 3, 3, 4, 5, 5, 6, 6];
+	const LETTERS: [(char, usize); 7] = [
+		('C', 0),
+		('D', 2),
+		('E', 4),
+		('F', 5),
+		('G', 7),
+		('A', 9),
+		('B', 11),
+	];
+	let fallback = || pitch_class_name(pitch_class, key_uses_flats(key)).to_string();
+	let key = key.trim();
+	let (Some((tonic, _)), Some(tonic_letter)) = (
+		parse_pitch_class(key),
+		key.chars()
+			.next()
+			.and_then(|first| LETTERS.iter().position(|(letter, _)| *letter == first)),
+	) else {
+		return fallback();
+	};
+	let interval = (pitch_class + 12 - tonic) % 12;
+	let (letter, natural) = LETTERS[(tonic_letter + DEGREE_LETTERS[interval]) % 7];
+	let accidental = match (pitch_class + 12 - natural) % 12 {
+		0 => "",
+		1 => "#",
+		11 => "b",
+		_ => return fallback(),
+	};
+	format!("{}{}", letter, accidental)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn names_keys_from_fifths() {
+		assert_eq!(key_name_from_fifths(0, false).as_deref(), Some("C"));
+		assert_eq!(key_name_from_fifths(-2, false).as_deref(), Some("Bb"));
+		assert_eq!(key_name_from_fifths(3, true).as_deref(), Some("F#m"));
+		assert_eq!(key_name_from_fifths(8, false), None);
+		assert_eq!(key_name_from_fifths(-8, true), None);
+	}
+
+	#[test]
+	fn spells_pitch_classes_in_keys() {
+		assert_eq!(spell_pitch_class(8, "Eb"), "Ab");
+		assert_eq!(spell_pitch_class(8, "E"), "G#");
+		assert_eq!(spell_pitch_class(11, "Gb"), "Cb");
+		assert_eq!(spell_pitch_class(5, "F#"), "E#");
+		assert_eq!(spell_pitch_class(3, "Cm"), "Eb");
+		// chromatic notes
+		assert_eq!(spell_pitch_class(1, "C"), "Db");
+		assert_eq!(spell_pitch_class(6, "C"), "F#");
+		assert_eq!(spell_pitch_class(10, "C"), "Bb");
+	}
+
+	#[test]
+	fn falls_back_to_the_usual_spelling() {
+		// G in C# would be F double sharp
+		assert_eq!(spell_pitch_class(7, "C#"), "G");
+		assert_eq!(spell_pitch_class(1, "not a key"), "C#");
+		assert_eq!(spell_pitch_class(1, ""), "C#");
+	}
+}
@@ -0,0 +1,48 @@
+/// The names of each pitch class (starting from C) using sharps.
+const SHARP_NAMES: [&str; 12] = [
+	"C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+/// The names of each pitch class (starting from C) using flats.
+const FLAT_NAMES: [&str; 12] = [
+	"C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B",
+];
+
+/// Returns the name of a pitch class (where 0 is C), like "F#" or "Gb".
+pub fn pitch_class_name(pitch_class: usize, flats: bool) -> &'static str {
+	if flats {
+		FLAT_NAMES[pitch_class % 12]
+	} else {
+		SHARP_NAMES[pitch_class % 12]
+	}
+}
+
+/// Reads the note name at the start of a key or chord name, returning
+/// its pitch class (where 0 is C) and the rest of the name. For example,
+/// "Bbm7" gives `(10, "m7")`.
+pub fn parse_pitch_class(name: &str) -> Option<(usize, &str)> {
+	let mut chars = name.chars();
+	let natural = match chars.next()? {
+		'C' => 0,
+		'D' => 2,
+		'E' => 4,
+		'F' => 5,
+		'G' => 7,
+		'A' => 9,
+		'B' => 11,
+		_ => return None,
+	};
+	let mut pitch_class: i32 = natural;
+	let mut rest = chars.as_str();
+	loop {
+		if let Some(after) = rest.strip_prefix('#').or_else(|| rest.strip_prefix('♯')) {
+			pitch_class += 1;
+			rest = after;
+		} else if let Some(after) = rest.strip_prefix('b').or_else(|| rest.strip_prefix('♭')) {
+			pitch_class -= 1;
+			rest = after;
+		} else {
+			break;
+		}
+	}
+	Some((pitch_class.rem_euclid(12) as usize, rest))
+}
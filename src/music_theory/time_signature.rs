@@ -22,14 +22,14 @@ impl TryFrom<&str> for TimeSignature {
 	type Error = anyhow::Error;
 
 	fn try_from(value: &str) -> anyhow::Result<Self> {
-		let regex = Regex::new("(\\d)+/(\\d)+").unwrap();
-		let captures = regex.captures(value).unwrap();
-		let top = captures[1]
-			.parse()
-			.map_err(|_| anyhow!("{} is not a valid time signature", value))?;
-		let bottom = captures[2]
-			.parse()
-			.map_err(|_| anyhow!("{} is not a valid time signature", value))?;
+		let regex = Regex::new("^\\s*(\\d+)\\s*/\\s*(\\d+)\\s*$").unwrap();
+		let invalid = || anyhow!("{} is not a valid time signature", value);
+		let captures = regex.captures(value).ok_or_else(invalid)?;
+		let top = captures[1].parse().map_err(|_| invalid())?;
+		let bottom = captures[2].parse().map_err(|_| invalid())?;
+		if top == 0 || bottom == 0 {
+			return Err(invalid());
+		}
 		Ok(Self { top, bottom })
 	}
 }
@@ -39,3 +39,38 @@ impl From<TimeSignature> for String {
 		format!("{}", value)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_time_signatures() {
+		let time_signature = TimeSignature::try_from(" 6 / 8 ").unwrap();
+		assert_eq!(time_signature, TimeSignature { top: 6, bottom: 8 });
+		assert_eq!(time_signature.to_string(), "6/8");
+		assert_eq!(
+			TimeSignature::try_from("12/16").unwrap(),
+			TimeSignature {
+				top: 12,
+				bottom: 16
+			}
+		);
+	}
+
+	#[test]
+	fn rejects_invalid_time_signatures() {
+		for value in [
+			"",
+			"4",
+			"4/",
+			"x/4",
+			"3/4 swing",
+			"0/4",
+			"4/0",
+			"99999999999/4",
+		] {
+			assert!(TimeSignature::try_from(value).is_err(), "{}", value);
+		}
+	}
+}
@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+use crate::shader_param::ShaderParam;
+
+/// A shader applied to the finished frame, including the widgets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostEffect {
+	pub effect: PostEffectKind,
+	/// Overrides the effect's default settings, like `intensity`.
+	#[serde(default)]
+	pub params: Vec<ShaderParam>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostEffectKind {
+	/// Makes bright colors glow. Params: `threshold`, `intensity`,
+	/// `radius`.
+	Bloom,
+	/// Splits the red and blue channels towards the edges. Params:
+	/// `amount`.
+	ChromaticAberration,
+	/// Curves the screen and adds scanlines. Params: `curvature`,
+	/// `scanline_intensity`, `scanline_count`.
+	Crt,
+	/// Adds animated film grain. Params: `amount`.
+	Grain,
+	/// Darkens the edges of the screen. Params: `intensity`, `softness`.
+	Vignette,
+}
+
+impl PostEffectKind {
+	/// Returns the source of the effect's fragment shader.
+	pub fn shader_source(self) -> &'static str {
+		match self {
+			Self::Bloom => include_str!("../resources/shaders/bloom.glsl"),
+			Self::ChromaticAberration => {
+				include_str!("../resources/shaders/chromatic_aberration.glsl")
+			}
+			Self::Crt => include_str!("../resources/shaders/crt.glsl"),
+			Self::Grain => include_str!("../resources/shaders/grain.glsl"),
+			Self::Vignette => include_str!("../resources/shaders/vignette.glsl"),
+		}
+	}
+}
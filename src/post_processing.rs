@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use anyhow::Context as AnyhowContext;
+use glam::Vec2;
+use micro::{
+	graphics::{mesh::Mesh, shader::Shader, Canvas, CanvasSettings},
+	math::Rect,
+	Context,
+};
+use palette::LinSrgba;
+
+use crate::{post_effect::PostEffect, shader_param::ShaderParam, BASE_RESOLUTION};
+
+/// The project's post-processing effects, applied in order to the
+/// finished frame.
+pub struct PostProcessing {
+	effects: Vec<(Shader, Vec<ShaderParam>)>,
+	/// Each effect draws to one canvas while reading the previous
+	/// effect's result from the other. `None` if there are no effects.
+	canvases: Option<[Canvas; 2]>,
+}
+
+impl PostProcessing {
+	pub fn load(ctx: &mut Context, effects: &[PostEffect]) -> anyhow::Result<Self> {
+		let effects = effects
+			.iter()
+			.map(|effect| -> anyhow::Result<(Shader, Vec<ShaderParam>)> {
+				let shader = Shader::from_fragment_str(ctx, effect.effect.shader_source())
+					.with_context(|| format!("error loading {:?} effect", effect.effect))?;
+				shader.send_vec3("iResolution", BASE_RESOLUTION.as_vec2().extend(1.0))?;
+				Ok((shader, effect.params.clone()))
+			})
+			.collect::<anyhow::Result<Vec<_>>>()?;
+		let canvases = (!effects.is_empty())
+			.then(|| [(); 2].map(|_| Canvas::new(ctx, BASE_RESOLUTION, CanvasSettings::default())));
+		Ok(Self { effects, canvases })
+	}
+
+	/// Applies each effect to a frame drawn to `source` and returns the
+	/// canvas with the result. Has to be called outside of other canvases.
+	pub fn apply<'a>(
+		&'a self,
+		ctx: &mut Context,
+		source: &'a Canvas,
+		time: Duration,
+	) -> anyhow::Result<&'a Canvas> {
+		let Some(canvases) = &self.canvases else {
+			return Ok(source);
+		};
+		let mut input = source;
+		for (i, (shader, params)) in self.effects.iter().enumerate() {
+			let output = &canvases[i % 2];
+			shader.send_f32("iTime", time.as_secs_f32())?;
+			shader.send_texture("iChannel0", input.texture())?;
+			for param in params {
+				param.send(shader)?;
+			}
+			output.render_to(ctx, |ctx| -> anyhow::Result<()> {
+				ctx.clear(LinSrgba::new(0.0, 0.0, 0.0, 0.0));
+				Mesh::rectangle(ctx, Rect::new(Vec2::ZERO, BASE_RESOLUTION.as_vec2()))
+					.draw(ctx, shader);
+				Ok(())
+			})?;
+			input = output;
+		}
+		Ok(input)
+	}
+}
@@ -1,16 +1,44 @@
-use std::path::{Path, PathBuf};
+use std::{
+	collections::BTreeMap,
+	path::{Path, PathBuf},
+};
 
 use serde::{Deserialize, Serialize};
 
-use crate::{shader_param::ShaderParam, user_track_info::UserTrackInfo};
+use crate::{
+	layout::{default_layout, PanelLayout},
+	post_effect::PostEffect,
+	shader_buffer::{ShaderBufferName, ShaderBufferSettings},
+	shader_channel::ShaderChannel,
+	shader_param::ShaderParam,
+	theme::ThemeSettings,
+	user_track_info::UserTrackInfo,
+};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Project {
 	pub audio_file_path: PathBuf,
 	pub shader_path: Option<PathBuf>,
+	/// An LRC file with timed lyrics for the song.
+	pub lyrics_path: Option<PathBuf>,
 	#[serde(default)]
 	pub shader_params: Vec<ShaderParam>,
+	/// The inputs sent to the shader as `iChannel0` to `iChannel3`.
+	#[serde(default)]
+	pub shader_channels: [Option<ShaderChannel>; 4],
+	/// Shaders drawn to offscreen buffers before the main shader each
+	/// frame. Only used if there's a main shader.
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub shader_buffers: BTreeMap<ShaderBufferName, ShaderBufferSettings>,
+	/// Effects applied in order to the finished frame, including
+	/// the widgets.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub post_processing: Vec<PostEffect>,
 	pub track_info: UserTrackInfo,
+	#[serde(default = "default_layout")]
+	pub layout: Vec<PanelLayout>,
+	#[serde(default)]
+	pub theme: ThemeSettings,
 }
 
 impl Project {
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::track_info::BeatInfo;
+
+/// Effects that make a panel react to the beat.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PulseSettings {
+	pub downbeat: DownbeatEffect,
+	/// How much the panel grows at the start of each beat, as a
+	/// fraction of its size. `0.0` disables the scale pulse.
+	pub beat_scale: f32,
+	/// Whether the panel's outline uses the accent color during the
+	/// first beat after a change.
+	pub accent_after_change: bool,
+	/// How long each pulse takes to fade out, in beats.
+	pub decay: f64,
+}
+
+impl PulseSettings {
+	pub fn effects(&self, beat_info: BeatInfo) -> PulseEffects {
+		let envelope = if self.decay > 0.0 {
+			(1.0 - beat_info.phase / self.decay).max(0.0) as f32
+		} else {
+			0.0
+		};
+		let downbeat_envelope = if beat_info.beat_in_bar == Some(0) {
+			envelope
+		} else {
+			0.0
+		};
+		PulseEffects {
+			stroke_width_multiplier: match self.downbeat {
+				DownbeatEffect::Thicken => 1.0 + downbeat_envelope,
+				_ => 1.0,
+			},
+			accent_amount: if self.accent_after_change && beat_info.beats_since_change < 1.0 {
+				1.0
+			} else if self.downbeat == DownbeatEffect::Flash {
+				downbeat_envelope
+			} else {
+				0.0
+			},
+			scale: 1.0 + self.beat_scale * envelope,
+		}
+	}
+}
+
+impl Default for PulseSettings {
+	fn default() -> Self {
+		Self {
+			downbeat: DownbeatEffect::None,
+			beat_scale: 0.0,
+			accent_after_change: false,
+			decay: 0.5,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownbeatEffect {
+	#[default]
+	None,
+	/// The outline gets thicker on the first beat of each bar.
+	Thicken,
+	/// The outline flashes the accent color on the first beat of each bar.
+	Flash,
+}
+
+/// The visual adjustments to apply to a panel on a given frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PulseEffects {
+	pub stroke_width_multiplier: f32,
+	/// How much to blend the outline from the foreground color to
+	/// the accent color (from 0 to 1).
+	pub accent_amount: f32,
+	pub scale: f32,
+}
@@ -1,15 +1,22 @@
 use std::{
 	path::Path,
 	process::{Child, Command, Stdio},
+	time::{Duration, SystemTime},
 };
 
 use micro::{graphics::SwapInterval, Context};
 
-use crate::{live_state::LiveState, loaded_project::LoadedProject, BASE_RESOLUTION, EXPORT_FPS};
+use crate::{
+	live_state::LiveState, loaded_project::LoadedProject, loaded_shader::ShaderFrame,
+	BASE_RESOLUTION, EXPORT_FPS,
+};
 
 pub struct RenderingState {
 	pub loaded_project: LoadedProject,
 	pub current_frame: u32,
+	/// The wall clock time when rendering started. Frames are dated as
+	/// if they were played back in real time from this point.
+	pub start_date: SystemTime,
 	pub canvas_read_buffer: Vec<u8>,
 	pub ffmpeg_process: Child,
 }
@@ -29,7 +36,7 @@ impl RenderingState {
 			.arg("-vcodec")
 			.arg("rawvideo")
 			.arg("-s")
-			.arg(&format!("{}x{}", BASE_RESOLUTION.x, BASE_RESOLUTION.y))
+			.arg(format!("{}x{}", BASE_RESOLUTION.x, BASE_RESOLUTION.y))
 			.arg("-pix_fmt")
 			.arg("rgba")
 			.arg("-r")
@@ -50,11 +57,37 @@ impl RenderingState {
 		Ok(Self {
 			loaded_project,
 			current_frame: 0,
+			start_date: SystemTime::now(),
 			canvas_read_buffer: vec![0; (BASE_RESOLUTION.x * BASE_RESOLUTION.y * 4) as usize],
 			ffmpeg_process,
 		})
 	}
 
+	/// Returns the position in the song of a frame of the video.
+	pub fn frame_time(frame: u32) -> Duration {
+		frame * Duration::from_secs_f64(1.0 / EXPORT_FPS)
+	}
+
+	/// Sends the shader uniforms for a frame of the video. Frames are
+	/// dated as if the video was played back from `start_date`.
+	pub fn send_frame_uniforms(
+		ctx: &Context,
+		loaded_project: &mut LoadedProject,
+		frame: u32,
+		start_date: SystemTime,
+	) -> anyhow::Result<()> {
+		let time_elapsed = Self::frame_time(frame);
+		loaded_project.send_shader_uniforms(
+			ctx,
+			ShaderFrame {
+				time: time_elapsed,
+				delta_time: Duration::from_secs_f64(1.0 / EXPORT_FPS),
+				frame,
+				date: start_date + time_elapsed,
+			},
+		)
+	}
+
 	pub fn cancel(mut self) -> anyhow::Result<LiveState> {
 		self.ffmpeg_process.kill().ok();
 		LiveState::from_loaded_project(self.loaded_project)
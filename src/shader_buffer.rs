@@ -0,0 +1,45 @@
+use std::{fmt::Display, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::shader_channel::ShaderChannel;
+
+/// One of the offscreen buffers shaders can draw to, like Shadertoy's
+/// Buffer A to D. Buffers are drawn in alphabetical order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShaderBufferName {
+	A,
+	B,
+	C,
+	D,
+}
+
+impl Display for ShaderBufferName {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let letter = match self {
+			Self::A => "A",
+			Self::B => "B",
+			Self::C => "C",
+			Self::D => "D",
+		};
+		f.write_fmt(format_args!("Buffer {}", letter))
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShaderBufferSettings {
+	/// The fragment shader that draws the buffer, relative to the
+	/// project file.
+	pub path: PathBuf,
+	/// The inputs sent to the shader as `iChannel0` to `iChannel3`.
+	#[serde(default)]
+	pub channels: [Option<ShaderChannel>; 4],
+	/// The size of the buffer as a fraction of the video resolution.
+	#[serde(default = "default_resolution_scale")]
+	pub resolution_scale: f32,
+}
+
+fn default_resolution_scale() -> f32 {
+	1.0
+}
@@ -0,0 +1,21 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::shader_buffer::ShaderBufferName;
+
+/// An input the shader can read as one of Shadertoy's `iChannel`
+/// textures.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum ShaderChannel {
+	/// An image file, relative to the project file.
+	Image { path: PathBuf },
+	/// The spectrum and waveform of the song, like Shadertoy's
+	/// audio input.
+	Audio,
+	/// The latest frame drawn to a buffer. A buffer reading itself or a
+	/// buffer drawn after it gets the previous frame.
+	Buffer { buffer: ShaderBufferName },
+}
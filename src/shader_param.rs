@@ -1,4 +1,5 @@
 use anyhow::bail;
+use micro::graphics::shader::Shader;
 use palette::{rgb::channels::Rgba, LinSrgba, Srgba};
 use serde::{Deserialize, Serialize};
 
@@ -9,6 +10,17 @@ pub struct ShaderParam {
 	pub kind: ShaderParamKind,
 }
 
+impl ShaderParam {
+	/// Sends the param's value to a shader as a uniform with the
+	/// param's name.
+	pub fn send(&self, shader: &Shader) -> anyhow::Result<()> {
+		match &self.kind {
+			ShaderParamKind::Float { value, .. } => shader.send_f32(&self.name, *value),
+			ShaderParamKind::Color { value } => shader.send_color(&self.name, value.0),
+		}
+	}
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
@@ -0,0 +1,169 @@
+use std::{collections::BTreeMap, path::Path, time::Duration};
+
+use anyhow::Context as AnyhowContext;
+use glam::{UVec2, Vec2};
+use micro::{
+	graphics::{mesh::Mesh, Canvas, CanvasSettings},
+	math::Rect,
+	Context,
+};
+use palette::LinSrgba;
+
+use crate::{
+	loaded_shader::{LoadedChannel, LoadedShader},
+	shader_buffer::{ShaderBufferName, ShaderBufferSettings},
+	shader_channel::ShaderChannel,
+	BASE_RESOLUTION,
+};
+
+/// The project's main shader and the buffers drawn before it, like the
+/// passes of a Shadertoy shader.
+pub struct ShaderPipeline {
+	/// The shader drawn behind the widgets.
+	pub image: LoadedShader,
+	/// Drawn in order before the image shader.
+	pub buffers: Vec<ShaderBuffer>,
+}
+
+pub struct ShaderBuffer {
+	pub name: ShaderBufferName,
+	pub shader: LoadedShader,
+	/// The buffer draws to one canvas while shaders read the previous
+	/// frame from the other.
+	canvases: [Canvas; 2],
+	/// The index of the canvas with the latest frame.
+	latest: usize,
+}
+
+impl ShaderBuffer {
+	fn latest_canvas(&self) -> &Canvas {
+		&self.canvases[self.latest]
+	}
+}
+
+impl ShaderPipeline {
+	/// Loads the image shader and each buffer. Paths are relative to
+	/// `base_dir`.
+	pub fn load(
+		ctx: &mut Context,
+		image_path: &Path,
+		image_channels: &[Option<ShaderChannel>; 4],
+		buffers: &BTreeMap<ShaderBufferName, ShaderBufferSettings>,
+		base_dir: &Path,
+	) -> anyhow::Result<Self> {
+		let buffer_resolutions = buffers
+			.iter()
+			.map(|(name, settings)| {
+				let resolution = (BASE_RESOLUTION.as_vec2() * settings.resolution_scale)
+					.round()
+					.as_uvec2()
+					.max(UVec2::ONE);
+				(*name, resolution)
+			})
+			.collect::<BTreeMap<_, _>>();
+		let image = LoadedShader::load(
+			ctx,
+			base_dir.join(image_path),
+			BASE_RESOLUTION,
+			image_channels,
+			base_dir,
+			&buffer_resolutions,
+		)?;
+		let buffers = buffers
+			.iter()
+			.map(|(name, settings)| -> anyhow::Result<ShaderBuffer> {
+				let resolution = buffer_resolutions[name];
+				let shader = LoadedShader::load(
+					ctx,
+					base_dir.join(&settings.path),
+					resolution,
+					&settings.channels,
+					base_dir,
+					&buffer_resolutions,
+				)
+				.with_context(|| format!("error loading {}", name))?;
+				Ok(ShaderBuffer {
+					name: *name,
+					shader,
+					canvases: [
+						cleared_canvas(ctx, resolution)?,
+						cleared_canvas(ctx, resolution)?,
+					],
+					latest: 0,
+				})
+			})
+			.collect::<anyhow::Result<Vec<_>>>()?;
+		Ok(Self { image, buffers })
+	}
+
+	/// Returns the buffer shaders followed by the image shader.
+	pub fn passes_mut(&mut self) -> impl Iterator<Item = &mut LoadedShader> {
+		self.buffers
+			.iter_mut()
+			.map(|buffer| &mut buffer.shader)
+			.chain(std::iter::once(&mut self.image))
+	}
+
+	/// Returns whether any pass reads the audio.
+	pub fn reads_audio(&self) -> bool {
+		self.image.reads_audio()
+			|| self
+				.buffers
+				.iter()
+				.any(|buffer| buffer.shader.reads_audio())
+	}
+
+	pub fn update_hot_reload(&mut self, ctx: &Context, delta_time: Duration) -> anyhow::Result<()> {
+		for buffer in &mut self.buffers {
+			buffer
+				.shader
+				.update_hot_reload(ctx, delta_time)
+				.with_context(|| format!("error reloading {}", buffer.name))?;
+		}
+		self.image.update_hot_reload(ctx, delta_time)
+	}
+
+	/// Draws the next frame of each buffer and sends the buffers to the
+	/// image shader. Has to be called outside of other canvases.
+	pub fn draw_buffers(&mut self, ctx: &mut Context) -> anyhow::Result<()> {
+		for i in 0..self.buffers.len() {
+			let buffer = &self.buffers[i];
+			send_buffer_channels(&buffer.shader, &self.buffers)?;
+			buffer.canvases[1 - buffer.latest].render_to(ctx, |ctx| -> anyhow::Result<()> {
+				ctx.clear(LinSrgba::new(0.0, 0.0, 0.0, 0.0));
+				Mesh::rectangle(
+					ctx,
+					Rect::new(Vec2::ZERO, buffer.shader.resolution.as_vec2()),
+				)
+				.draw(ctx, &buffer.shader.shader);
+				Ok(())
+			})?;
+			self.buffers[i].latest = 1 - self.buffers[i].latest;
+		}
+		send_buffer_channels(&self.image, &self.buffers)
+	}
+}
+
+fn cleared_canvas(ctx: &mut Context, size: UVec2) -> anyhow::Result<Canvas> {
+	let canvas = Canvas::new(ctx, size, CanvasSettings::default());
+	canvas.render_to(ctx, |ctx| -> anyhow::Result<()> {
+		ctx.clear(LinSrgba::new(0.0, 0.0, 0.0, 0.0));
+		Ok(())
+	})?;
+	Ok(canvas)
+}
+
+/// Sends the latest frame of each buffer a shader reads.
+fn send_buffer_channels(shader: &LoadedShader, buffers: &[ShaderBuffer]) -> anyhow::Result<()> {
+	for (i, channel) in shader.channels.iter().enumerate() {
+		let Some(LoadedChannel::Buffer { name, .. }) = channel else {
+			continue;
+		};
+		// buffer channels are checked when the shader is loaded
+		let buffer = buffers.iter().find(|buffer| buffer.name == *name).unwrap();
+		shader
+			.shader
+			.send_texture(&format!("iChannel{}", i), buffer.latest_canvas().texture())?;
+	}
+	Ok(())
+}
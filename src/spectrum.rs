@@ -0,0 +1,57 @@
+use std::{sync::Arc, time::Duration};
+
+use kira::sound::static_sound::StaticSoundData;
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+/// Calculates the frequency content of a song at a given time.
+///
+/// The analysis only depends on the audio data and the timestamp, so the
+/// live preview and exported videos always show the same spectrum.
+pub struct SpectrumAnalyzer {
+	fft: Arc<dyn Fft<f32>>,
+	window: Vec<f32>,
+	buffer: Vec<Complex<f32>>,
+}
+
+impl SpectrumAnalyzer {
+	pub fn new(fft_size: usize) -> Self {
+		let window = (0..fft_size)
+			.map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / fft_size as f32).cos())
+			.collect();
+		Self {
+			fft: FftPlanner::new().plan_fft_forward(fft_size),
+			window,
+			buffer: vec![Complex::default(); fft_size],
+		}
+	}
+
+	pub fn fft_size(&self) -> usize {
+		self.window.len()
+	}
+
+	/// Returns the magnitudes of the first half of the FFT bins for the
+	/// audio leading up to `timestamp`, scaled so a full-scale sine
+	/// wave is about `1.0`.
+	pub fn magnitudes(&mut self, sound_data: &StaticSoundData, timestamp: Duration) -> Vec<f32> {
+		let fft_size = self.fft_size();
+		let end_frame = (timestamp.as_secs_f64() * sound_data.sample_rate as f64) as isize;
+		let start_frame = end_frame - fft_size as isize;
+		for (i, (sample, window)) in self.buffer.iter_mut().zip(&self.window).enumerate() {
+			let frame_index = start_frame + i as isize;
+			let value = usize::try_from(frame_index)
+				.ok()
+				.and_then(|frame_index| sound_data.frames.get(frame_index))
+				.map(|frame| (frame.left + frame.right) / 2.0)
+				.unwrap_or(0.0);
+			*sample = Complex::new(value * window, 0.0);
+		}
+		self.fft.process(&mut self.buffer);
+		// a hann-windowed sine splits its energy between the positive and
+		// negative frequencies and loses half of it to the window
+		let scale = 4.0 / fft_size as f32;
+		self.buffer[..fft_size / 2]
+			.iter()
+			.map(|bin| bin.norm() * scale)
+			.collect()
+	}
+}
@@ -0,0 +1,142 @@
+use palette::LinSrgba;
+use serde::{Deserialize, Serialize};
+
+use crate::{fonts::FontsSettings, shader_param::ShaderColor};
+
+/// The visual style of the widgets, as resolved from a project's
+/// [`ThemeSettings`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+	/// The color the canvas is cleared to.
+	pub background: LinSrgba,
+	/// The color of panel outlines, label boxes and widget contents.
+	pub foreground: LinSrgba,
+	/// The color of the text inside panel labels.
+	pub label: LinSrgba,
+	/// The color used to highlight the active part of a widget.
+	pub accent: LinSrgba,
+	pub stroke_width: f32,
+	/// The size of a single layout grid cell in pixels.
+	pub grid_cell_size: f32,
+	pub label_padding: f32,
+	pub corner_cut: CornerCut,
+	pub fonts: FontsSettings,
+}
+
+impl Theme {
+	pub fn light() -> Self {
+		Self {
+			background: LinSrgba::new(0.8, 0.8, 0.8, 1.0),
+			foreground: LinSrgba::new(0.0, 0.0, 0.0, 1.0),
+			label: LinSrgba::new(0.8, 0.8, 0.8, 1.0),
+			accent: LinSrgba::new(0.0, 0.0, 0.0, 1.0),
+			stroke_width: 8.0,
+			grid_cell_size: 48.0,
+			label_padding: 16.0,
+			corner_cut: CornerCut::Diagonal,
+			fonts: FontsSettings::default(),
+		}
+	}
+
+	pub fn dark() -> Self {
+		Self {
+			background: LinSrgba::new(0.01, 0.01, 0.012, 1.0),
+			foreground: LinSrgba::new(0.8, 0.8, 0.8, 1.0),
+			label: LinSrgba::new(0.01, 0.01, 0.012, 1.0),
+			accent: LinSrgba::new(0.9, 0.45, 0.05, 1.0),
+			..Self::light()
+		}
+	}
+
+	pub fn high_contrast() -> Self {
+		Self {
+			background: LinSrgba::new(0.0, 0.0, 0.0, 1.0),
+			foreground: LinSrgba::new(1.0, 1.0, 1.0, 1.0),
+			label: LinSrgba::new(0.0, 0.0, 0.0, 1.0),
+			accent: LinSrgba::new(1.0, 1.0, 0.0, 1.0),
+			stroke_width: 12.0,
+			corner_cut: CornerCut::None,
+			..Self::light()
+		}
+	}
+}
+
+impl Default for Theme {
+	fn default() -> Self {
+		Self::light()
+	}
+}
+
+/// The theme section of a project file: a built-in preset, optionally
+/// with some of its values overridden.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ThemeSettings {
+	#[serde(default)]
+	pub preset: ThemePreset,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub background: Option<ShaderColor>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub foreground: Option<ShaderColor>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub label: Option<ShaderColor>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub accent: Option<ShaderColor>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub stroke_width: Option<f32>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub grid_cell_size: Option<f32>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub label_padding: Option<f32>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub corner_cut: Option<CornerCut>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub fonts: Option<FontsSettings>,
+}
+
+impl ThemeSettings {
+	pub fn theme(&self) -> Theme {
+		let preset = self.preset.theme();
+		Theme {
+			background: self.background.map_or(preset.background, |color| color.0),
+			foreground: self.foreground.map_or(preset.foreground, |color| color.0),
+			label: self.label.map_or(preset.label, |color| color.0),
+			accent: self.accent.map_or(preset.accent, |color| color.0),
+			stroke_width: self.stroke_width.unwrap_or(preset.stroke_width),
+			grid_cell_size: self.grid_cell_size.unwrap_or(preset.grid_cell_size),
+			label_padding: self.label_padding.unwrap_or(preset.label_padding),
+			corner_cut: self.corner_cut.unwrap_or(preset.corner_cut),
+			fonts: self.fonts.clone().unwrap_or(preset.fonts),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThemePreset {
+	#[default]
+	Light,
+	Dark,
+	HighContrast,
+}
+
+impl ThemePreset {
+	pub fn theme(self) -> Theme {
+		match self {
+			ThemePreset::Light => Theme::light(),
+			ThemePreset::Dark => Theme::dark(),
+			ThemePreset::HighContrast => Theme::high_contrast(),
+		}
+	}
+}
+
+/// Which corners of a panel's outline are cut off diagonally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CornerCut {
+	/// Square corners.
+	None,
+	/// The top left and bottom right corners are cut.
+	Diagonal,
+	/// All four corners are cut.
+	All,
+}
@@ -36,14 +36,83 @@ impl TrackInfo {
 			.unwrap()
 	}
 
-	pub fn current_beat(&self, timestamp: Duration) -> Option<f64> {
+	/// Returns a value derived from the music state at the given timestamp,
+	/// along with the value it had before it last changed and how long ago
+	/// that change happened.
+	pub fn value_change<T: PartialEq>(
+		&self,
+		timestamp: Duration,
+		value: impl Fn(&MusicState) -> T,
+	) -> ValueChange<T> {
+		let mut index = self
+			.music_states
+			.iter()
+			.rposition(|state| state.timestamp <= timestamp)
+			.unwrap();
+		let current = value(&self.music_states[index].music_state);
+		let mut previous = None;
+		while index > 0 {
+			let previous_value = value(&self.music_states[index - 1].music_state);
+			if previous_value != current {
+				previous = Some(previous_value);
+				break;
+			}
+			index -= 1;
+		}
+		ValueChange {
+			current,
+			previous,
+			time_since_change: timestamp - self.music_states[index].timestamp,
+		}
+	}
+
+	pub fn beat_info(&self, timestamp: Duration) -> BeatInfo {
 		let state = self.music_state(timestamp);
-		state.music_state.time_signature.map(|time_signature| {
+		let beats_per_second = state.music_state.bpm / 60.0;
+		let beats_since_change = (timestamp - state.timestamp).as_secs_f64() * beats_per_second;
+		BeatInfo {
+			beats_since_change,
+			beat_in_bar: state.music_state.time_signature.map(|time_signature| {
+				(beats_since_change as u64 % time_signature.top.max(1) as u64) as u32
+			}),
+			phase: beats_since_change.fract(),
+		}
+	}
+
+	/// Returns the index of the current beat and bar counting from the
+	/// start of the song. Like [`TrackInfo::beat_info`], the beat in the
+	/// bar starts over at each change.
+	pub fn beat_index(&self, timestamp: Duration) -> BeatIndex {
+		let mut index = BeatIndex { beat: 0, bar: 0 };
+		for (i, state) in self.music_states.iter().enumerate() {
+			if state.timestamp > timestamp {
+				break;
+			}
 			let beats_per_second = state.music_state.bpm / 60.0;
-			let total_beats_elapsed =
-				(timestamp - state.timestamp).as_secs_f64() * beats_per_second;
-			total_beats_elapsed % time_signature.top as f64
-		})
+			if !beats_per_second.is_finite() || beats_per_second <= 0.0 {
+				continue;
+			}
+			let beats_per_bar = state
+				.music_state
+				.time_signature
+				.map_or(1, |time_signature| time_signature.top.max(1) as u64);
+			match self.music_states.get(i + 1) {
+				Some(next_state) if next_state.timestamp <= timestamp => {
+					// count every beat and bar that starts before the next change
+					let duration = (next_state.timestamp - state.timestamp).as_secs_f64();
+					let beats = (duration * beats_per_second - 1e-9).ceil().max(0.0) as u64;
+					index.beat += beats;
+					index.bar += beats.div_ceil(beats_per_bar);
+				}
+				_ => {
+					let beats_since_change =
+						((timestamp - state.timestamp).as_secs_f64() * beats_per_second) as u64;
+					index.beat += beats_since_change;
+					index.bar += beats_since_change / beats_per_bar;
+				}
+			}
+		}
+		index
 	}
 }
 
@@ -53,6 +122,35 @@ pub struct TimestampedMusicState {
 	pub music_state: MusicState,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BeatInfo {
+	/// The number of beats since the most recent change.
+	pub beats_since_change: f64,
+	/// The index of the current beat within the bar, or `None`
+	/// if there's no time signature.
+	pub beat_in_bar: Option<u32>,
+	/// How far into the current beat we are (from 0 to 1).
+	pub phase: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeatIndex {
+	/// The number of beats before the current one.
+	pub beat: u64,
+	/// The number of bars before the current one. Every beat starts a
+	/// bar when there's no time signature.
+	pub bar: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueChange<T> {
+	pub current: T,
+	/// The value before the most recent change, or `None` if the value
+	/// hasn't changed since the start of the track.
+	pub previous: Option<T>,
+	pub time_since_change: Duration,
+}
+
 fn tick_duration(bpm: f64, ticks_per_beat: u32) -> Duration {
 	Duration::from_secs_f64(60.0 / bpm / ticks_per_beat as f64)
 }
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// How a widget animates from its previous value to a new one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransitionSettings {
+	pub kind: TransitionKind,
+	/// The length of the transition in seconds.
+	pub duration: f64,
+}
+
+impl TransitionSettings {
+	/// Returns how far along the transition is (from 0 to 1) a given
+	/// amount of time after the value changed, with easing applied.
+	pub fn progress(&self, time_since_change: Duration) -> f32 {
+		if self.kind == TransitionKind::None || self.duration <= 0.0 {
+			return 1.0;
+		}
+		let linear = (time_since_change.as_secs_f64() / self.duration).clamp(0.0, 1.0) as f32;
+		linear * linear * (3.0 - 2.0 * linear)
+	}
+}
+
+impl Default for TransitionSettings {
+	fn default() -> Self {
+		Self {
+			kind: TransitionKind::None,
+			duration: 0.25,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransitionKind {
+	/// The new value replaces the old one immediately.
+	#[default]
+	None,
+	/// The old value fades out while the new one fades in.
+	Crossfade,
+	/// The old value slides out of the top of the panel while
+	/// the new one slides in from the bottom.
+	Slide,
+	/// The old value folds away vertically, then the new
+	/// one unfolds.
+	Flip,
+	/// Numbers count from the old value to the new one. Values
+	/// that aren't numbers use a crossfade instead.
+	CountUp,
+}
@@ -1,3 +1,5 @@
+pub mod analysis;
+pub mod import_export;
 pub mod main_menu;
 pub mod shader_params_editor;
 
@@ -0,0 +1,307 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use egui::{DragValue, Grid, RichText, ScrollArea, Ui, Window};
+
+use crate::{
+	analysis::{
+		beat_tracking::{BeatTracking, BeatTrackingSettings},
+		chord_recognition::{ChordRecognition, ChordRecognitionSettings},
+		chroma::Chromagram,
+		key_detection::{KeyDetection, KeyDetectionSettings},
+	},
+	format::format_time,
+	live_state::LiveState,
+	loaded_project::LoadedProject,
+	MainState, Mode,
+};
+
+use super::show_dialog_if_error;
+
+/// Settings and results of the audio analyses, kept while the app
+/// is open.
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisState {
+	/// The project the results were calculated for.
+	project_path: Option<PathBuf>,
+	beat_tracking_settings: BeatTrackingSettings,
+	beat_tracking: Option<BeatTracking>,
+	/// Calculated the first time an analysis needs it.
+	chromagram: Option<Chromagram>,
+	key_detection_settings: KeyDetectionSettings,
+	key_detection: Option<KeyDetection>,
+	chord_recognition_settings: ChordRecognitionSettings,
+	chord_recognition: Option<ChordRecognition>,
+}
+
+impl AnalysisState {
+	fn chromagram(&mut self, loaded_project: &LoadedProject) -> &Chromagram {
+		self.chromagram
+			.get_or_insert_with(|| Chromagram::analyze(&loaded_project.sound_data))
+	}
+}
+
+impl MainState {
+	pub fn render_analysis_window(&mut self, egui_ctx: &egui::Context) {
+		Window::new("Analysis")
+			.open(&mut self.show_analysis_window)
+			.enabled(!matches!(&self.mode, Mode::Rendering(..)))
+			.show(egui_ctx, |ui| {
+				let Mode::Live(LiveState { loaded_project, .. }) = &mut self.mode else {
+					ui.label("No project loaded");
+					return;
+				};
+				let state = &mut self.analysis_state;
+				if state.project_path.as_ref() != Some(&loaded_project.project_path) {
+					*state = AnalysisState {
+						project_path: Some(loaded_project.project_path.clone()),
+						beat_tracking_settings: state.beat_tracking_settings,
+						key_detection_settings: state.key_detection_settings,
+						chord_recognition_settings: state.chord_recognition_settings,
+						..Default::default()
+					};
+				}
+				show_dialog_if_error(render_beat_tracking_section(ui, loaded_project, state));
+				ui.separator();
+				show_dialog_if_error(render_key_detection_section(ui, loaded_project, state));
+				ui.separator();
+				show_dialog_if_error(render_chord_recognition_section(ui, loaded_project, state));
+				ui.separator();
+				ui.label("Applied results replace the project's track info.");
+				if ui.button("Save project").clicked() {
+					show_dialog_if_error(
+						loaded_project
+							.project
+							.save(&loaded_project.project_path)
+							.context("error saving project"),
+					);
+				}
+			});
+	}
+}
+
+fn render_beat_tracking_section(
+	ui: &mut Ui,
+	loaded_project: &mut LoadedProject,
+	state: &mut AnalysisState,
+) -> anyhow::Result<()> {
+	let settings = &mut state.beat_tracking_settings;
+	ui.heading("Beat tracking");
+	ui.horizontal(|ui| {
+		ui.label("Tempo range");
+		ui.add(DragValue::new(&mut settings.min_bpm).clamp_range(20.0..=settings.max_bpm));
+		ui.label("to");
+		ui.add(DragValue::new(&mut settings.max_bpm).clamp_range(settings.min_bpm..=400.0));
+		ui.label("BPM");
+	});
+	ui.horizontal(|ui| {
+		let mut guess_beats_per_bar = settings.beats_per_bar.is_none();
+		ui.checkbox(&mut guess_beats_per_bar, "Guess beats per bar");
+		settings.beats_per_bar = if guess_beats_per_bar {
+			None
+		} else {
+			let mut beats_per_bar = settings.beats_per_bar.unwrap_or(4);
+			ui.add(DragValue::new(&mut beats_per_bar).clamp_range(1..=16));
+			Some(beats_per_bar)
+		};
+	});
+	ui.horizontal(|ui| {
+		ui.label("Drift tolerance (beats)");
+		ui.add(
+			DragValue::new(&mut settings.drift_tolerance)
+				.speed(0.01)
+				.clamp_range(0.0..=1.0),
+		);
+	});
+	ui.checkbox(
+		&mut settings.keep_chords_and_keys,
+		"Keep chords, keys and sections",
+	);
+	if ui.button("Detect beats").clicked() {
+		state.beat_tracking = Some(
+			BeatTracking::analyze(&loaded_project.sound_data, *settings)
+				.context("error detecting beats")?,
+		);
+	}
+	let Some(beat_tracking) = &state.beat_tracking else {
+		return Ok(());
+	};
+	let report = beat_tracking.report;
+	Grid::new("beat_tracking_report").show(ui, |ui| {
+		ui.label("Beats");
+		ui.label(beat_tracking.beats.len().to_string());
+		ui.end_row();
+		ui.label("Time signature");
+		ui.label(format!("{}/4", beat_tracking.beats_per_bar));
+		ui.end_row();
+		ui.label("First downbeat");
+		ui.label(format!(
+			"{:.2}s",
+			beat_tracking.beats[beat_tracking.first_downbeat]
+		));
+		ui.end_row();
+		ui.label("Average tempo");
+		ui.label(format!("{:.1} BPM", report.average_bpm));
+		ui.end_row();
+		ui.label("Tempo range");
+		ui.label(format!(
+			"{:.1} to {:.1} BPM",
+			report.min_bpm, report.max_bpm
+		));
+		ui.end_row();
+		ui.label("Tempo confidence");
+		ui.label(format!("{:.0}%", report.tempo_confidence * 100.0));
+		ui.end_row();
+		ui.label("Beat confidence");
+		ui.label(format!("{:.0}%", report.beat_confidence * 100.0));
+		ui.end_row();
+		ui.label("Downbeat confidence");
+		ui.label(format!("{:.0}%", report.downbeat_confidence * 100.0));
+		ui.end_row();
+	});
+	if ui.button("Apply tempo map").clicked() {
+		loaded_project.set_user_track_info(
+			beat_tracking
+				.tempo_map(&loaded_project.track_info, state.beat_tracking_settings)
+				.context("error applying tempo map")?,
+		);
+	}
+	Ok(())
+}
+
+fn render_key_detection_section(
+	ui: &mut Ui,
+	loaded_project: &mut LoadedProject,
+	state: &mut AnalysisState,
+) -> anyhow::Result<()> {
+	let settings = &mut state.key_detection_settings;
+	ui.heading("Key detection");
+	ui.horizontal(|ui| {
+		ui.label("Window length (seconds)");
+		ui.add(DragValue::new(&mut settings.window_duration).clamp_range(1.0..=60.0));
+	});
+	ui.horizontal(|ui| {
+		ui.label("Key change penalty");
+		ui.add(
+			DragValue::new(&mut settings.change_penalty)
+				.speed(0.1)
+				.clamp_range(0.0..=100.0),
+		);
+	});
+	if ui.button("Detect key").clicked() {
+		let settings = *settings;
+		let chromagram = state.chromagram(loaded_project);
+		state.key_detection = Some(KeyDetection::analyze(chromagram, settings));
+	}
+	let Some(key_detection) = &state.key_detection else {
+		return Ok(());
+	};
+	ui.label(format!(
+		"Overall key: {} ({:.0}% fit)",
+		key_detection.global_key.name(),
+		key_detection.global_key.correlation * 100.0
+	));
+	Grid::new("key_detection_segments").show(ui, |ui| {
+		ui.label("Start");
+		ui.label("Key");
+		ui.label("Fit");
+		ui.end_row();
+		for segment in &key_detection.segments {
+			ui.label(format_time(segment.start, 0..=0));
+			ui.label(segment.key.name());
+			ui.label(format!("{:.0}%", segment.key.correlation * 100.0));
+			ui.end_row();
+		}
+	});
+	if ui.button("Apply suggested keys").clicked() {
+		loaded_project.set_user_track_info(
+			key_detection
+				.suggested_track_info(
+					&loaded_project.project.track_info,
+					loaded_project.sound_data.duration(),
+				)
+				.context("error applying suggested keys")?,
+		);
+	}
+	Ok(())
+}
+
+fn render_chord_recognition_section(
+	ui: &mut Ui,
+	loaded_project: &mut LoadedProject,
+	state: &mut AnalysisState,
+) -> anyhow::Result<()> {
+	let settings = &mut state.chord_recognition_settings;
+	ui.heading("Chord recognition");
+	ui.label("Chords are placed on the beats of the current tempo map.");
+	ui.horizontal(|ui| {
+		ui.label("Chord change penalty");
+		ui.add(
+			DragValue::new(&mut settings.change_penalty)
+				.speed(0.01)
+				.clamp_range(0.0..=10.0),
+		);
+	});
+	ui.checkbox(
+		&mut settings.downbeats_only,
+		"Only change chords on downbeats",
+	);
+	ui.checkbox(
+		&mut settings.extended_chords,
+		"Recognize seventh, diminished and suspended chords",
+	);
+	if ui.button("Recognize chords").clicked() {
+		let settings = *settings;
+		let chromagram = state.chromagram(loaded_project);
+		state.chord_recognition = Some(ChordRecognition::analyze(
+			chromagram,
+			&loaded_project.track_info,
+			loaded_project.sound_data.duration(),
+			settings,
+		));
+	}
+	let Some(chord_recognition) = &state.chord_recognition else {
+		return Ok(());
+	};
+	let diffs = chord_recognition.diff(&loaded_project.track_info);
+	let num_different = diffs
+		.iter()
+		.filter(|diff| diff.current != diff.proposed)
+		.count();
+	ui.label(format!(
+		"{} chords proposed, {} of {} parts differ from the current chords",
+		chord_recognition.chords.len(),
+		num_different,
+		diffs.len()
+	));
+	ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+		Grid::new("chord_recognition_diff")
+			.striped(true)
+			.show(ui, |ui| {
+				ui.label("Time");
+				ui.label("Current");
+				ui.label("Proposed");
+				ui.end_row();
+				for diff in &diffs {
+					let current = diff.current.as_deref().unwrap_or("-");
+					let proposed = diff.proposed.as_deref().unwrap_or("-");
+					ui.label(format!("{:.2}s", diff.start.as_secs_f64()));
+					ui.label(current);
+					if diff.current == diff.proposed {
+						ui.label(proposed);
+					} else {
+						ui.label(RichText::new(proposed).color(ui.visuals().warn_fg_color));
+					}
+					ui.end_row();
+				}
+			});
+	});
+	if ui.button("Apply proposed chords").clicked() {
+		loaded_project.set_user_track_info(
+			chord_recognition
+				.suggested_track_info(&loaded_project.project.track_info)
+				.context("error applying proposed chords")?,
+		);
+	}
+	Ok(())
+}
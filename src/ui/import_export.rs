@@ -0,0 +1,242 @@
+use anyhow::Context;
+use egui::{DragValue, Ui, Window};
+use rfd::FileDialog;
+
+use crate::{
+	analysis::midi_chords::{infer_midi_chords, MidiChordSettings},
+	interchange::{
+		chordpro::{export_chordpro, import_chordpro, ChordProSettings},
+		markers::{
+			export_audacity_labels, export_reaper_csv, import_audacity_labels, import_reaper_csv,
+		},
+		musicxml::{import_musicxml, MusicXmlSettings},
+	},
+	live_state::LiveState,
+	loaded_project::LoadedProject,
+	MainState, Mode,
+};
+
+use super::show_dialog_if_error;
+
+/// Settings for the importers and exporters that are remembered
+/// while the app is open.
+#[derive(Debug, Clone, Default)]
+pub struct ImportExportSettings {
+	pub chordpro: ChordProSettings,
+	pub musicxml: MusicXmlSettings,
+	pub midi_chords: MidiChordSettings,
+}
+
+impl MainState {
+	pub fn render_import_export_window(&mut self, egui_ctx: &egui::Context) {
+		Window::new("Import/Export")
+			.open(&mut self.show_import_export_window)
+			.enabled(!matches!(&self.mode, Mode::Rendering(..)))
+			.show(egui_ctx, |ui| {
+				let Mode::Live(LiveState { loaded_project, .. }) = &mut self.mode else {
+					ui.label("No project loaded");
+					return;
+				};
+				show_dialog_if_error(render_chordpro_section(
+					ui,
+					loaded_project,
+					&mut self.import_export_settings.chordpro,
+				));
+				ui.separator();
+				show_dialog_if_error(render_musicxml_section(
+					ui,
+					loaded_project,
+					&mut self.import_export_settings.musicxml,
+				));
+				ui.separator();
+				show_dialog_if_error(render_midi_chords_section(
+					ui,
+					loaded_project,
+					&mut self.import_export_settings.midi_chords,
+				));
+				ui.separator();
+				show_dialog_if_error(render_markers_section(ui, loaded_project));
+				ui.separator();
+				ui.label("Imported track info replaces the project's track info.");
+				if ui.button("Save project").clicked() {
+					show_dialog_if_error(
+						loaded_project
+							.project
+							.save(&loaded_project.project_path)
+							.context("error saving project"),
+					);
+				}
+			});
+	}
+}
+
+fn render_chordpro_section(
+	ui: &mut Ui,
+	loaded_project: &mut LoadedProject,
+	settings: &mut ChordProSettings,
+) -> anyhow::Result<()> {
+	ui.heading("ChordPro");
+	ui.horizontal(|ui| {
+		ui.label("Beats per bar (if the song has no {time})");
+		ui.add(DragValue::new(&mut settings.bar_length).clamp_range(1..=32));
+	});
+	ui.horizontal(|ui| -> anyhow::Result<()> {
+		if ui.button("Import...").clicked() {
+			if let Some(path) = FileDialog::new()
+				.set_directory(loaded_project.project_path.parent().unwrap())
+				.add_filter(
+					"ChordPro file",
+					&["cho", "chopro", "chordpro", "crd", "pro"],
+				)
+				.pick_file()
+			{
+				let chordpro =
+					std::fs::read_to_string(path).context("error reading ChordPro file")?;
+				loaded_project.set_user_track_info(
+					import_chordpro(&chordpro, *settings)
+						.context("error importing ChordPro file")?,
+				);
+			}
+		}
+		if ui.button("Export...").clicked() {
+			if let Some(path) = FileDialog::new()
+				.set_directory(loaded_project.project_path.parent().unwrap())
+				.add_filter("ChordPro file", &["cho"])
+				.save_file()
+			{
+				let chordpro = export_chordpro(
+					&loaded_project.track_info,
+					loaded_project.sound_data.duration(),
+					*settings,
+				);
+				std::fs::write(path, chordpro).context("error writing ChordPro file")?;
+			}
+		}
+		Ok(())
+	})
+	.inner
+}
+
+fn render_musicxml_section(
+	ui: &mut Ui,
+	loaded_project: &mut LoadedProject,
+	settings: &mut MusicXmlSettings,
+) -> anyhow::Result<()> {
+	ui.heading("MusicXML");
+	ui.horizontal(|ui| {
+		ui.label("Part");
+		// shown starting from 1 like in notation programs
+		let mut part_number = settings.part_index + 1;
+		ui.add(DragValue::new(&mut part_number).clamp_range(1..=usize::MAX));
+		settings.part_index = part_number - 1;
+	});
+	if ui.button("Import...").clicked() {
+		if let Some(path) = FileDialog::new()
+			.set_directory(loaded_project.project_path.parent().unwrap())
+			.add_filter("MusicXML file", &["musicxml", "xml"])
+			.pick_file()
+		{
+			let musicxml = std::fs::read_to_string(path).context("error reading MusicXML file")?;
+			loaded_project.set_user_track_info(
+				import_musicxml(&musicxml, *settings).context("error importing MusicXML file")?,
+			);
+		}
+	}
+	Ok(())
+}
+
+fn render_midi_chords_section(
+	ui: &mut Ui,
+	loaded_project: &mut LoadedProject,
+	settings: &mut MidiChordSettings,
+) -> anyhow::Result<()> {
+	ui.heading("MIDI chords");
+	ui.label(
+		"Infers chords from a MIDI file that lines up with the song. Only the chords are replaced.",
+	);
+	ui.checkbox(&mut settings.one_chord_per_bar, "One chord per bar");
+	if ui.button("Import...").clicked() {
+		if let Some(path) = FileDialog::new()
+			.set_directory(loaded_project.project_path.parent().unwrap())
+			.add_filter("MIDI file", &["mid", "midi"])
+			.pick_file()
+		{
+			let midi = std::fs::read(path).context("error reading MIDI file")?;
+			loaded_project.set_user_track_info(
+				infer_midi_chords(
+					&midi,
+					&loaded_project.project.track_info,
+					loaded_project.sound_data.duration(),
+					*settings,
+				)
+				.context("error importing MIDI file")?,
+			);
+		}
+	}
+	Ok(())
+}
+
+fn render_markers_section(ui: &mut Ui, loaded_project: &mut LoadedProject) -> anyhow::Result<()> {
+	ui.heading("DAW markers");
+	ui.label("Markers named like chord:Am7, key:F, bpm:92, time:3/4 or section:Chorus");
+	ui.horizontal(|ui| -> anyhow::Result<()> {
+		ui.label("Reaper CSV");
+		if ui.button("Import...").clicked() {
+			if let Some(path) = FileDialog::new()
+				.set_directory(loaded_project.project_path.parent().unwrap())
+				.add_filter("CSV file", &["csv"])
+				.pick_file()
+			{
+				let csv = std::fs::read_to_string(path).context("error reading CSV file")?;
+				loaded_project.set_user_track_info(
+					import_reaper_csv(&csv).context("error importing Reaper markers")?,
+				);
+			}
+		}
+		if ui.button("Export...").clicked() {
+			if let Some(path) = FileDialog::new()
+				.set_directory(loaded_project.project_path.parent().unwrap())
+				.add_filter("CSV file", &["csv"])
+				.save_file()
+			{
+				let csv = export_reaper_csv(
+					&loaded_project.track_info,
+					loaded_project.sound_data.duration(),
+				);
+				std::fs::write(path, csv).context("error writing CSV file")?;
+			}
+		}
+		Ok(())
+	})
+	.inner?;
+	ui.horizontal(|ui| -> anyhow::Result<()> {
+		ui.label("Audacity labels");
+		if ui.button("Import...").clicked() {
+			if let Some(path) = FileDialog::new()
+				.set_directory(loaded_project.project_path.parent().unwrap())
+				.add_filter("Label file", &["txt"])
+				.pick_file()
+			{
+				let labels = std::fs::read_to_string(path).context("error reading label file")?;
+				loaded_project.set_user_track_info(
+					import_audacity_labels(&labels).context("error importing Audacity labels")?,
+				);
+			}
+		}
+		if ui.button("Export...").clicked() {
+			if let Some(path) = FileDialog::new()
+				.set_directory(loaded_project.project_path.parent().unwrap())
+				.add_filter("Label file", &["txt"])
+				.save_file()
+			{
+				let labels = export_audacity_labels(
+					&loaded_project.track_info,
+					loaded_project.sound_data.duration(),
+				);
+				std::fs::write(path, labels).context("error writing label file")?;
+			}
+		}
+		Ok(())
+	})
+	.inner
+}
@@ -73,6 +73,12 @@ impl MainState {
 					if ui.button("Shader Params...").clicked() {
 						self.show_shader_params_editor = true;
 					}
+					if ui.button("Import/Export...").clicked() {
+						self.show_import_export_window = true;
+					}
+					if ui.button("Analysis...").clicked() {
+						self.show_analysis_window = true;
+					}
 					Ok(())
 				})
 				.inner?;
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::{music_state::MusicState, music_theory::TimeSignature};
@@ -11,7 +13,7 @@ pub struct UserTrackInfo {
 	pub changes: Vec<Change>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct Change {
 	pub after: u32,
 	pub bpm: Option<f64>,
@@ -34,4 +36,13 @@ pub struct Change {
 		with = "::serde_with::rust::double_option"
 	)]
 	pub chord: Option<Option<String>>,
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		with = "::serde_with::rust::double_option"
+	)]
+	pub section: Option<Option<String>>,
+	/// Shows or hides image widgets by ID.
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub image_visibility: BTreeMap<String, bool>,
 }
@@ -1,259 +1,506 @@
 mod beat_indicator;
+mod bpm;
+mod chord;
+mod image;
+mod key;
+mod loudness;
+mod lyrics;
+mod metronome;
+mod spectrum;
+mod text_cache;
+mod timeline;
 
-use std::time::Duration;
+use std::{collections::HashMap, path::Path, rc::Rc, sync::OnceLock, time::Duration};
 
-use glam::Vec2;
+use anyhow::anyhow;
+use glam::{Affine2, Vec2};
+use kira::sound::static_sound::StaticSoundData;
 use micro::{
 	graphics::{
 		mesh::{Mesh, ShapeStyle},
 		text::{LayoutSettings, Text, TextFragment},
-		ColorConstants, DrawParams, StencilAction, StencilTest,
+		DrawParams, StencilAction, StencilTest,
 	},
 	math::{Rect, VecConstants},
 	Context,
 };
 use palette::LinSrgba;
 use regex::Regex;
+use serde::de::DeserializeOwned;
 
-use crate::{track_info::TrackInfo, Fonts, FLAT_SYMBOL, OFFWHITE, SHARP_SYMBOL};
+use crate::{
+	fonts::Fonts,
+	layout::PanelLayout,
+	loudness::LoudnessAnalysis,
+	lyrics::Lyrics,
+	music_state::MusicState,
+	theme::{CornerCut, Theme},
+	track_info::{BeatInfo, TrackInfo, ValueChange},
+	transition::{TransitionKind, TransitionSettings},
+	FLAT_SYMBOL, SHARP_SYMBOL,
+};
+
+pub use self::text_cache::set_text_cache_enabled;
 
-use self::beat_indicator::{draw_beat_indicator, draw_beat_indicator_placeholder};
+use self::{
+	bpm::BpmWidget,
+	chord::ChordWidget,
+	image::ImageWidget,
+	key::KeyWidget,
+	loudness::LoudnessWidget,
+	lyrics::LyricsWidget,
+	metronome::MetronomeWidget,
+	spectrum::SpectrumWidget,
+	text_cache::{TextCache, TextStyle},
+	timeline::TimelineWidget,
+};
 
-const GRID_CELL_SIZE: f32 = 48.0;
-const STROKE_WIDTH: f32 = 8.0;
-const PANEL_LABEL_PADDING: f32 = 16.0;
 const PLACEHOLDER_STRING: &str = "---";
 
-pub(super) fn draw_panel(
+/// Something that can be drawn inside a panel.
+///
+/// Widgets are created from the options in a project's layout, and
+/// [`WidgetRegistry::register`] makes them available under [`Widget::NAME`].
+pub trait Widget {
+	/// The value of the `type` field that selects this widget in a layout.
+	/// Also used as the panel's title if the layout doesn't specify one.
+	const NAME: &'static str;
+
+	/// Per-widget settings read from the panel's entry in the layout.
+	type Options: DeserializeOwned;
+
+	fn new(options: Self::Options) -> Self;
+
+	/// Loads any files the widget needs. Paths in the options are
+	/// relative to `project_dir`.
+	fn load(&mut self, _ctx: &mut Context, _project_dir: &Path) -> anyhow::Result<()> {
+		Ok(())
+	}
+
+	/// The size of the panel in grid cells, used if the layout
+	/// doesn't specify one.
+	fn size(&self) -> Vec2;
+
+	/// Whether the panel should be drawn at all on this frame.
+	fn visible(&self, _frame: &FrameContext) -> bool {
+		true
+	}
+
+	/// Draws the widget's contents. `grid_bounds` is the panel's
+	/// rectangle in grid cells.
+	fn draw(
+		&mut self,
+		ctx: &mut Context,
+		frame: &FrameContext,
+		grid_bounds: Rect,
+	) -> anyhow::Result<()>;
+}
+
+/// An object-safe version of [`Widget`] so that panels can
+/// hold any kind of widget.
+pub trait DynWidget {
+	fn load(&mut self, ctx: &mut Context, project_dir: &Path) -> anyhow::Result<()>;
+
+	fn size(&self) -> Vec2;
+
+	fn visible(&self, frame: &FrameContext) -> bool;
+
+	fn draw(
+		&mut self,
+		ctx: &mut Context,
+		frame: &FrameContext,
+		grid_bounds: Rect,
+	) -> anyhow::Result<()>;
+}
+
+impl<T: Widget> DynWidget for T {
+	fn load(&mut self, ctx: &mut Context, project_dir: &Path) -> anyhow::Result<()> {
+		Widget::load(self, ctx, project_dir)
+	}
+
+	fn size(&self) -> Vec2 {
+		Widget::size(self)
+	}
+
+	fn visible(&self, frame: &FrameContext) -> bool {
+		Widget::visible(self, frame)
+	}
+
+	fn draw(
+		&mut self,
+		ctx: &mut Context,
+		frame: &FrameContext,
+		grid_bounds: Rect,
+	) -> anyhow::Result<()> {
+		Widget::draw(self, ctx, frame, grid_bounds)
+	}
+}
+
+/// Everything widgets need to know about the current frame.
+pub struct FrameContext<'a> {
+	pub timestamp: Duration,
+	pub track_info: &'a TrackInfo,
+	pub music_state: &'a MusicState,
+	pub beat_info: BeatInfo,
+	pub sound_data: &'a StaticSoundData,
+	pub loudness: &'a LoudnessAnalysis,
+	pub lyrics: Option<&'a Lyrics>,
+	pub theme: &'a Theme,
+	pub fonts: &'a Fonts,
+}
+
+impl<'a> FrameContext<'a> {
+	pub fn new(
+		timestamp: Duration,
+		track_info: &'a TrackInfo,
+		sound_data: &'a StaticSoundData,
+		loudness: &'a LoudnessAnalysis,
+		lyrics: Option<&'a Lyrics>,
+		theme: &'a Theme,
+		fonts: &'a Fonts,
+	) -> Self {
+		Self {
+			timestamp,
+			track_info,
+			music_state: &track_info.music_state(timestamp).music_state,
+			beat_info: track_info.beat_info(timestamp),
+			sound_data,
+			loudness,
+			lyrics,
+			theme,
+			fonts,
+		}
+	}
+}
+
+/// A widget along with its position in the layout.
+pub struct Panel {
+	pub layout: PanelLayout,
+	pub widget: Box<dyn DynWidget>,
+	text_cache: TextCache,
+}
+
+impl Panel {
+	pub fn grid_bounds(&self) -> Rect {
+		let default_size = self.widget.size();
+		Rect::new(
+			Vec2::new(self.layout.x, self.layout.y),
+			Vec2::new(
+				self.layout.width.unwrap_or(default_size.x),
+				self.layout.height.unwrap_or(default_size.y),
+			),
+		)
+	}
+
+	pub fn draw(&mut self, ctx: &mut Context, frame: &FrameContext) -> anyhow::Result<()> {
+		if !self.widget.visible(frame) {
+			return Ok(());
+		}
+		let grid_bounds = self.grid_bounds();
+		let title = self
+			.text_cache
+			.get(ctx, frame, TextStyle::Label, self.layout.title());
+		let widget = &mut self.widget;
+		draw_panel(
+			ctx,
+			frame,
+			&self.layout,
+			grid_bounds,
+			&title,
+			|ctx, grid_bounds| widget.draw(ctx, frame, grid_bounds),
+		)
+	}
+}
+
+type WidgetConstructor = fn(serde_json::Value) -> anyhow::Result<Box<dyn DynWidget>>;
+
+/// Creates widgets by name from the entries in a project's layout.
+pub struct WidgetRegistry {
+	constructors: HashMap<&'static str, WidgetConstructor>,
+}
+
+impl WidgetRegistry {
+	pub fn new() -> Self {
+		Self {
+			constructors: HashMap::new(),
+		}
+	}
+
+	pub fn register<W: Widget + 'static>(&mut self) {
+		self.constructors.insert(W::NAME, construct_widget::<W>);
+	}
+
+	pub fn create_panel(
+		&self,
+		ctx: &mut Context,
+		layout: &PanelLayout,
+		project_dir: &Path,
+	) -> anyhow::Result<Panel> {
+		let constructor = self
+			.constructors
+			.get(layout.widget_type.as_str())
+			.ok_or_else(|| anyhow!("unknown widget type \"{}\"", layout.widget_type))?;
+		let mut widget = constructor(serde_json::Value::Object(layout.options.clone()))
+			.map_err(|err| anyhow!("invalid options for {} widget: {}", layout.widget_type, err))?;
+		widget.load(ctx, project_dir)?;
+		Ok(Panel {
+			layout: layout.clone(),
+			widget,
+			text_cache: TextCache::default(),
+		})
+	}
+}
+
+impl Default for WidgetRegistry {
+	fn default() -> Self {
+		let mut registry = Self::new();
+		registry.register::<BpmWidget>();
+		registry.register::<MetronomeWidget>();
+		registry.register::<KeyWidget>();
+		registry.register::<ChordWidget>();
+		registry.register::<TimelineWidget>();
+		registry.register::<SpectrumWidget>();
+		registry.register::<LoudnessWidget>();
+		registry.register::<ImageWidget>();
+		registry.register::<LyricsWidget>();
+		registry
+	}
+}
+
+fn construct_widget<W: Widget + 'static>(
+	options: serde_json::Value,
+) -> anyhow::Result<Box<dyn DynWidget>> {
+	Ok(Box::new(W::new(serde_json::from_value(options)?)))
+}
+
+fn draw_panel(
 	ctx: &mut Context,
-	fonts: &Fonts,
-	title: &str,
+	frame: &FrameContext,
+	layout: &PanelLayout,
 	grid_bounds: Rect,
+	title: &Text,
 	mut content: impl FnMut(&mut Context, Rect) -> anyhow::Result<()>,
 ) -> anyhow::Result<()> {
-	let polygon_grid_points = [
-		grid_bounds.top_left + Vec2::RIGHT,
-		grid_bounds.top_right(),
-		grid_bounds.bottom_right() + Vec2::UP,
-		grid_bounds.bottom_right() + Vec2::LEFT,
-		grid_bounds.bottom_left(),
-		grid_bounds.top_left + Vec2::DOWN,
-	];
-	Mesh::simple_polygon(
-		ctx,
-		ShapeStyle::Stroke(STROKE_WIDTH),
-		polygon_grid_points
-			.iter()
-			.map(|point| *point * GRID_CELL_SIZE),
-		LinSrgba::BLACK,
-	)?
-	.draw(ctx, DrawParams::new());
-	let text = Text::new(ctx, &fonts.small, title, LayoutSettings::default());
-	let text_position = text_translation(
-		&text,
-		(grid_bounds.top_left + Vec2::RIGHT * 1.5) * GRID_CELL_SIZE,
-		Vec2::new(0.0, 0.5),
-	);
-	Mesh::styled_rectangle(
-		ctx,
-		ShapeStyle::Fill,
-		text.bounds()
-			.unwrap()
-			.translated(text_position)
-			.padded(Vec2::splat(PANEL_LABEL_PADDING)),
-		LinSrgba::BLACK,
-	)?
-	.draw(ctx, DrawParams::new());
-	text.draw(
-		ctx,
-		DrawParams::new().translated(text_position).color(OFFWHITE),
-	);
-	ctx.clear_stencil();
-	ctx.write_to_stencil(StencilAction::Replace(1), |ctx| -> anyhow::Result<()> {
+	let theme = frame.theme;
+	let effects = layout.pulse.effects(frame.beat_info);
+	let outline_color = mix(theme.foreground, theme.accent, effects.accent_amount);
+	let center = grid_bounds.center() * theme.grid_cell_size;
+	let transform = Affine2::from_translation(center)
+		* Affine2::from_scale(Vec2::splat(effects.scale))
+		* Affine2::from_translation(-center);
+	ctx.with_transform(transform, |ctx| -> anyhow::Result<()> {
+		let polygon_grid_points = panel_outline_grid_points(grid_bounds, theme.corner_cut);
 		Mesh::simple_polygon(
 			ctx,
-			ShapeStyle::Fill,
+			ShapeStyle::Stroke(theme.stroke_width * effects.stroke_width_multiplier),
 			polygon_grid_points
 				.iter()
-				.map(|point| *point * GRID_CELL_SIZE),
-			LinSrgba::BLACK,
+				.map(|point| *point * theme.grid_cell_size),
+			outline_color,
 		)?
 		.draw(ctx, DrawParams::new());
-		Ok(())
-	})?;
-	ctx.with_stencil(StencilTest::Equal, 1, |ctx| -> anyhow::Result<()> {
-		content(ctx, grid_bounds)?;
+		let text_position = text_translation(
+			title,
+			(grid_bounds.top_left + Vec2::RIGHT * 1.5) * theme.grid_cell_size,
+			Vec2::new(0.0, 0.5),
+		);
+		Mesh::styled_rectangle(
+			ctx,
+			ShapeStyle::Fill,
+			title
+				.bounds()
+				.unwrap()
+				.translated(text_position)
+				.padded(Vec2::splat(theme.label_padding)),
+			outline_color,
+		)?
+		.draw(ctx, DrawParams::new());
+		title.draw(
+			ctx,
+			DrawParams::new()
+				.translated(text_position)
+				.color(theme.label),
+		);
+		ctx.clear_stencil();
+		ctx.write_to_stencil(StencilAction::Replace(1), |ctx| -> anyhow::Result<()> {
+			Mesh::simple_polygon(
+				ctx,
+				ShapeStyle::Fill,
+				polygon_grid_points
+					.iter()
+					.map(|point| *point * theme.grid_cell_size),
+				theme.foreground,
+			)?
+			.draw(ctx, DrawParams::new());
+			Ok(())
+		})?;
+		ctx.with_stencil(StencilTest::Equal, 1, |ctx| -> anyhow::Result<()> {
+			content(ctx, grid_bounds)?;
+			Ok(())
+		})?;
 		Ok(())
 	})?;
 	Ok(())
 }
 
-pub(super) fn draw_bpm_panel(
-	ctx: &mut Context,
-	track_info: &TrackInfo,
-	timestamp: Duration,
-	fonts: &Fonts,
-	position: Vec2,
-) -> Result<(), anyhow::Error> {
-	draw_panel(
-		ctx,
-		fonts,
-		"bpm",
-		Rect::new(position, Vec2::new(12.0, 4.0)),
-		|ctx, grid_bounds| {
-			let music_state = &track_info.music_state(timestamp).music_state;
-			let bpm_string = if music_state.bpm_hidden {
-				PLACEHOLDER_STRING.to_string()
-			} else {
-				music_state.bpm.to_string()
-			};
-			let text = Text::new(ctx, &fonts.large, &bpm_string, LayoutSettings::default());
-			text.draw(
-				ctx,
-				DrawParams::new()
-					.translated(text_translation(
-						&text,
-						grid_bounds.center() * GRID_CELL_SIZE,
-						Vec2::splat(0.5),
-					))
-					.color(LinSrgba::BLACK),
-			);
-			Ok(())
-		},
-	)?;
-	Ok(())
+fn panel_outline_grid_points(grid_bounds: Rect, corner_cut: CornerCut) -> Vec<Vec2> {
+	match corner_cut {
+		CornerCut::None => vec![
+			grid_bounds.top_left,
+			grid_bounds.top_right(),
+			grid_bounds.bottom_right(),
+			grid_bounds.bottom_left(),
+		],
+		CornerCut::Diagonal => vec![
+			grid_bounds.top_left + Vec2::RIGHT,
+			grid_bounds.top_right(),
+			grid_bounds.bottom_right() + Vec2::UP,
+			grid_bounds.bottom_right() + Vec2::LEFT,
+			grid_bounds.bottom_left(),
+			grid_bounds.top_left + Vec2::DOWN,
+		],
+		CornerCut::All => vec![
+			grid_bounds.top_left + Vec2::RIGHT,
+			grid_bounds.top_right() + Vec2::LEFT,
+			grid_bounds.top_right() + Vec2::DOWN,
+			grid_bounds.bottom_right() + Vec2::UP,
+			grid_bounds.bottom_right() + Vec2::LEFT,
+			grid_bounds.bottom_left() + Vec2::RIGHT,
+			grid_bounds.bottom_left() + Vec2::UP,
+			grid_bounds.top_left + Vec2::DOWN,
+		],
+	}
 }
 
-pub(super) fn draw_metronome_panel(
+/// Draws text centered in a rectangle (in pixels), animating between
+/// the text for the previous and current value of a [`ValueChange`].
+fn draw_transitioned_text<T>(
 	ctx: &mut Context,
-	track_info: &TrackInfo,
-	timestamp: Duration,
-	fonts: &Fonts,
-	position: Vec2,
-) -> Result<(), anyhow::Error> {
-	draw_panel(
-		ctx,
-		fonts,
-		"metronome",
-		Rect::new(position, Vec2::new(12.0, 5.0)),
-		|ctx, grid_bounds| {
-			let text_region = grid_bounds.resized_y(4.0, 0.0);
-			let text = Text::new(
+	theme: &Theme,
+	transition: TransitionSettings,
+	change: &ValueChange<T>,
+	rect: Rect,
+	mut text: impl FnMut(&mut Context, &T) -> Rc<Text>,
+) {
+	let progress = transition.progress(change.time_since_change);
+	let current_text = text(ctx, &change.current);
+	let previous = match &change.previous {
+		Some(previous) if progress < 1.0 => previous,
+		_ => {
+			draw_centered_text(
+				ctx,
+				&current_text,
+				rect.center(),
+				0.0,
+				1.0,
+				theme.foreground,
+			);
+			return;
+		}
+	};
+	let previous_text = text(ctx, previous);
+	match transition.kind {
+		TransitionKind::None => {
+			draw_centered_text(
 				ctx,
-				&fonts.large,
-				&track_info
-					.music_state(timestamp)
-					.music_state
-					.time_signature
-					.map(|time_signature| time_signature.to_string())
-					.unwrap_or_else(|| "---".to_string()),
-				LayoutSettings::default(),
+				&current_text,
+				rect.center(),
+				0.0,
+				1.0,
+				theme.foreground,
 			);
-			text.draw(
+		}
+		TransitionKind::Crossfade | TransitionKind::CountUp => {
+			draw_centered_text(
 				ctx,
-				DrawParams::new()
-					.translated(text_translation(
-						&text,
-						text_region.center() * GRID_CELL_SIZE,
-						Vec2::splat(0.5),
-					))
-					.color(LinSrgba::BLACK),
+				&previous_text,
+				rect.center(),
+				0.0,
+				1.0,
+				with_alpha(theme.foreground, 1.0 - progress),
 			);
-			if let (Some(time_signature), Some(current_beat)) = (
-				track_info.music_state(timestamp).music_state.time_signature,
-				track_info.current_beat(timestamp),
-			) {
-				draw_beat_indicator(
+			draw_centered_text(
+				ctx,
+				&current_text,
+				rect.center(),
+				0.0,
+				1.0,
+				with_alpha(theme.foreground, progress),
+			);
+		}
+		TransitionKind::Slide => {
+			draw_centered_text(
+				ctx,
+				&previous_text,
+				rect.center(),
+				-progress * rect.size.y,
+				1.0,
+				theme.foreground,
+			);
+			draw_centered_text(
+				ctx,
+				&current_text,
+				rect.center(),
+				(1.0 - progress) * rect.size.y,
+				1.0,
+				theme.foreground,
+			);
+		}
+		TransitionKind::Flip => {
+			if progress < 0.5 {
+				draw_centered_text(
 					ctx,
-					grid_bounds.resized_y(1.0, 1.0),
-					time_signature,
-					current_beat as u32,
-				)?;
+					&previous_text,
+					rect.center(),
+					0.0,
+					1.0 - progress * 2.0,
+					theme.foreground,
+				);
 			} else {
-				draw_beat_indicator_placeholder(ctx, grid_bounds.resized_y(1.0, 1.0))?;
+				draw_centered_text(
+					ctx,
+					&current_text,
+					rect.center(),
+					0.0,
+					progress * 2.0 - 1.0,
+					theme.foreground,
+				);
 			}
-			Ok(())
-		},
-	)?;
-	Ok(())
+		}
+	}
 }
 
-pub(super) fn draw_key_panel(
+fn draw_centered_text(
 	ctx: &mut Context,
-	track_info: &TrackInfo,
-	timestamp: Duration,
-	fonts: &Fonts,
-	position: Vec2,
-) -> Result<(), anyhow::Error> {
-	draw_panel(
+	text: &Text,
+	center: Vec2,
+	offset_y: f32,
+	scale_y: f32,
+	color: LinSrgba,
+) {
+	let translation = text_translation(text, center, Vec2::splat(0.5));
+	text.draw(
 		ctx,
-		fonts,
-		"key",
-		Rect::new(position, Vec2::new(12.0, 4.0)),
-		|ctx, grid_bounds| {
-			let text = chord_text(
-				ctx,
-				track_info
-					.music_state(timestamp)
-					.music_state
-					.key
-					.as_deref()
-					.unwrap_or("---"),
-				fonts,
-			);
-			text.draw(
-				ctx,
-				DrawParams::new()
-					.translated(text_translation(
-						&text,
-						grid_bounds.center() * GRID_CELL_SIZE,
-						Vec2::splat(0.5),
-					))
-					.color(LinSrgba::BLACK),
-			);
-			Ok(())
-		},
-	)?;
-	Ok(())
+		DrawParams::new()
+			.translated(translation - center)
+			.scaled(Vec2::new(1.0, scale_y))
+			.translated(center + Vec2::new(0.0, offset_y))
+			.color(color),
+	);
 }
 
-pub(super) fn draw_chord_panel(
-	ctx: &mut Context,
-	track_info: &TrackInfo,
-	timestamp: Duration,
-	fonts: &Fonts,
-	position: Vec2,
-) -> Result<(), anyhow::Error> {
-	draw_panel(
-		ctx,
-		fonts,
-		"chord",
-		Rect::new(position, Vec2::new(12.0, 4.0)),
-		|ctx, grid_bounds| {
-			let text = chord_text(
-				ctx,
-				track_info
-					.music_state(timestamp)
-					.music_state
-					.chord
-					.as_deref()
-					.unwrap_or("---"),
-				fonts,
-			);
-			text.draw(
-				ctx,
-				DrawParams::new()
-					.translated(text_translation(
-						&text,
-						grid_bounds.center() * GRID_CELL_SIZE,
-						Vec2::splat(0.5),
-					))
-					.color(LinSrgba::BLACK),
-			);
-			Ok(())
-		},
-	)?;
-	Ok(())
+fn mix(a: LinSrgba, b: LinSrgba, amount: f32) -> LinSrgba {
+	LinSrgba::new(
+		a.red + (b.red - a.red) * amount,
+		a.green + (b.green - a.green) * amount,
+		a.blue + (b.blue - a.blue) * amount,
+		a.alpha + (b.alpha - a.alpha) * amount,
+	)
+}
+
+fn with_alpha(mut color: LinSrgba, alpha: f32) -> LinSrgba {
+	color.alpha *= alpha;
+	color
 }
 
 fn text_translation(text: &Text, target_position: Vec2, anchor: Vec2) -> Vec2 {
@@ -292,7 +539,8 @@ fn split_chord_str(chord: &str) -> Vec<(ChordTextFont, String)> {
 	if chord == PLACEHOLDER_STRING {
 		return vec![(ChordTextFont::Big, chord.to_string())];
 	}
-	let regex = Regex::new("([ABCDEFG][b#]?)(.*)").unwrap();
+	static REGEX: OnceLock<Regex> = OnceLock::new();
+	let regex = REGEX.get_or_init(|| Regex::new("([ABCDEFG][b#]?)(.*)").unwrap());
 	let captures = regex.captures(chord).expect("invalid chord");
 	let big_text = &captures[1];
 	let small_text = &captures[2];
@@ -1,63 +1,550 @@
+use std::f32::consts::{PI, TAU};
+
 use glam::Vec2;
 use micro::{
 	graphics::{
 		mesh::{Mesh, MeshBuilder, ShapeStyle},
-		ColorConstants, DrawParams,
+		DrawParams,
 	},
 	math::Rect,
 	Context,
 };
 use palette::LinSrgba;
+use serde::{Deserialize, Serialize};
+
+use crate::{music_theory::TimeSignature, theme::Theme};
+
+use super::with_alpha;
+
+/// The number of line segments used to draw curves.
+const CURVE_SEGMENTS: usize = 48;
+/// How far the pendulum swings to either side, in radians.
+const PENDULUM_MAX_ANGLE: f32 = PI / 6.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BeatIndicatorSettings {
+	pub style: BeatIndicatorStyle,
+	/// How many parts to split each beat into. `1` doesn't show
+	/// any subdivisions.
+	pub subdivisions: u32,
+}
 
-use crate::music_theory::TimeSignature;
+impl Default for BeatIndicatorSettings {
+	fn default() -> Self {
+		Self {
+			style: BeatIndicatorStyle::Bar,
+			subdivisions: 1,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BeatIndicatorStyle {
+	/// A sheared bar with the cell for the current beat filled in.
+	#[default]
+	Bar,
+	/// A pendulum that reaches the end of its swing on every beat.
+	Pendulum,
+	/// A baton tracing the conducting pattern for the time signature.
+	Conductor,
+	/// A row of dots with the current beat lit.
+	Dots,
+	/// A circle that fills in over the course of the bar.
+	Sweep,
+}
 
-use super::{GRID_CELL_SIZE, STROKE_WIDTH};
+impl BeatIndicatorStyle {
+	/// Whether the indicator is drawn in a single row of grid cells
+	/// rather than a square.
+	pub fn is_horizontal(self) -> bool {
+		matches!(self, Self::Bar | Self::Dots)
+	}
+}
 
 pub fn draw_beat_indicator(
 	ctx: &mut Context,
+	theme: &Theme,
+	settings: BeatIndicatorSettings,
 	rect: Rect,
 	time_signature: TimeSignature,
 	current_beat: u32,
+	phase: f64,
 ) -> anyhow::Result<()> {
 	let mut mesh_builder = MeshBuilder::new();
-	let num_ticks = time_signature.top;
+	let position = BeatPosition {
+		num_beats: time_signature.top.max(1),
+		current_beat,
+		phase: phase as f32,
+		subdivisions: settings.subdivisions.max(1),
+	};
+	match settings.style {
+		BeatIndicatorStyle::Bar => add_bar(&mut mesh_builder, theme, rect, position)?,
+		BeatIndicatorStyle::Pendulum => add_pendulum(&mut mesh_builder, theme, rect, position)?,
+		BeatIndicatorStyle::Conductor => add_conductor(&mut mesh_builder, theme, rect, position)?,
+		BeatIndicatorStyle::Dots => add_dots(&mut mesh_builder, theme, rect, position)?,
+		BeatIndicatorStyle::Sweep => add_sweep(&mut mesh_builder, theme, rect, position)?,
+	}
+	mesh_builder.build(ctx).draw(ctx, DrawParams::new());
+	Ok(())
+}
+
+pub fn draw_beat_indicator_placeholder(
+	ctx: &mut Context,
+	theme: &Theme,
+	style: BeatIndicatorStyle,
+	rect: Rect,
+) -> anyhow::Result<()> {
+	if style.is_horizontal() {
+		Mesh::styled_rectangle(
+			ctx,
+			ShapeStyle::Stroke(theme.stroke_width),
+			Rect::new(
+				rect.top_left * theme.grid_cell_size,
+				rect.size * theme.grid_cell_size,
+			),
+			theme.foreground,
+		)?
+		.draw(ctx, DrawParams::new());
+	} else {
+		let (center, radius) = circle_in_rect(theme, rect);
+		Mesh::circle(
+			ctx,
+			ShapeStyle::Stroke(theme.stroke_width),
+			center,
+			radius,
+			theme.foreground,
+		)?
+		.draw(ctx, DrawParams::new());
+	}
+	Ok(())
+}
+
+/// Where we are in the bar, as needed by each indicator style.
+#[derive(Debug, Clone, Copy)]
+struct BeatPosition {
+	num_beats: u32,
+	current_beat: u32,
+	/// How far into the current beat we are (from 0 to 1).
+	phase: f32,
+	subdivisions: u32,
+}
+
+impl BeatPosition {
+	fn current_subdivision(self) -> u32 {
+		((self.phase * self.subdivisions as f32) as u32).min(self.subdivisions - 1)
+	}
+
+	/// How far into the bar we are (from 0 to 1).
+	fn bar_progress(self) -> f32 {
+		(self.current_beat as f32 + self.phase) / self.num_beats as f32
+	}
+}
+
+fn add_bar(
+	mesh_builder: &mut MeshBuilder,
+	theme: &Theme,
+	rect: Rect,
+	position: BeatPosition,
+) -> anyhow::Result<()> {
+	let num_ticks = position.num_beats;
 	mesh_builder.add_rectangle(
-		ShapeStyle::Stroke(STROKE_WIDTH),
-		Rect::new(rect.top_left * GRID_CELL_SIZE, rect.size * GRID_CELL_SIZE),
-		LinSrgba::BLACK,
+		ShapeStyle::Stroke(theme.stroke_width),
+		Rect::new(
+			rect.top_left * theme.grid_cell_size,
+			rect.size * theme.grid_cell_size,
+		),
+		theme.foreground,
 	)?;
 	for i in 1..num_ticks {
 		mesh_builder.add_simple_polyline(
-			STROKE_WIDTH,
-			beat_indicator_tick_points(rect, i, num_ticks),
-			LinSrgba::BLACK,
+			theme.stroke_width,
+			beat_indicator_tick_points(theme, rect, i as f32, num_ticks),
+			theme.foreground,
 		)?;
 	}
-	let current_tick = beat_indicator_tick_points(rect, current_beat, num_ticks);
-	let next_tick = beat_indicator_tick_points(rect, current_beat + 1, num_ticks);
+	let current_beat = position.current_beat as f32;
+	if position.subdivisions == 1 {
+		add_bar_segment(
+			mesh_builder,
+			theme,
+			rect,
+			num_ticks,
+			current_beat,
+			current_beat + 1.0,
+			theme.accent,
+		)?;
+		return Ok(());
+	}
+	add_bar_segment(
+		mesh_builder,
+		theme,
+		rect,
+		num_ticks,
+		current_beat,
+		current_beat + 1.0,
+		with_alpha(theme.accent, 0.25),
+	)?;
+	let subdivision_length = 1.0 / position.subdivisions as f32;
+	let subdivision_start =
+		current_beat + position.current_subdivision() as f32 * subdivision_length;
+	add_bar_segment(
+		mesh_builder,
+		theme,
+		rect,
+		num_ticks,
+		subdivision_start,
+		subdivision_start + subdivision_length,
+		theme.accent,
+	)?;
+	for i in 1..position.subdivisions {
+		mesh_builder.add_simple_polyline(
+			theme.stroke_width / 2.0,
+			beat_indicator_tick_points(
+				theme,
+				rect,
+				current_beat + i as f32 * subdivision_length,
+				num_ticks,
+			),
+			theme.foreground,
+		)?;
+	}
+	Ok(())
+}
+
+fn add_bar_segment(
+	mesh_builder: &mut MeshBuilder,
+	theme: &Theme,
+	rect: Rect,
+	num_ticks: u32,
+	start: f32,
+	end: f32,
+	color: LinSrgba,
+) -> anyhow::Result<()> {
+	let start_tick = beat_indicator_tick_points(theme, rect, start, num_ticks);
+	let end_tick = beat_indicator_tick_points(theme, rect, end, num_ticks);
 	mesh_builder.add_simple_polygon(
 		ShapeStyle::Fill,
-		[current_tick[0], next_tick[0], next_tick[1], current_tick[1]],
-		LinSrgba::BLACK,
+		[start_tick[0], end_tick[0], end_tick[1], start_tick[1]],
+		color,
 	)?;
-	mesh_builder.build(ctx).draw(ctx, DrawParams::new());
 	Ok(())
 }
 
-pub fn draw_beat_indicator_placeholder(ctx: &mut Context, rect: Rect) -> anyhow::Result<()> {
-	Mesh::styled_rectangle(
-		ctx,
-		ShapeStyle::Stroke(STROKE_WIDTH),
-		Rect::new(rect.top_left * GRID_CELL_SIZE, rect.size * GRID_CELL_SIZE),
-		LinSrgba::BLACK,
-	)?
-	.draw(ctx, DrawParams::new());
+fn add_pendulum(
+	mesh_builder: &mut MeshBuilder,
+	theme: &Theme,
+	rect: Rect,
+	position: BeatPosition,
+) -> anyhow::Result<()> {
+	let (center, radius) = circle_in_rect(theme, rect);
+	let pivot = center - Vec2::new(0.0, radius);
+	let length = radius * 2.0;
+	let bob_radius = radius / 6.0;
+	let swing_radius = length - bob_radius;
+	// the pendulum swings from one side to the other over each beat,
+	// so it's on the right for even beats and on the left for odd beats
+	let angle_at = |phase: f32| {
+		let side = if position.current_beat.is_multiple_of(2) {
+			1.0
+		} else {
+			-1.0
+		};
+		side * PENDULUM_MAX_ANGLE * (PI * phase).cos()
+	};
+	let point_on_swing =
+		|angle: f32, distance: f32| pivot + Vec2::new(angle.sin(), angle.cos()) * distance;
+	mesh_builder.add_simple_polyline(
+		theme.stroke_width / 2.0,
+		(0..=CURVE_SEGMENTS).map(|i| {
+			let angle = PENDULUM_MAX_ANGLE * (i as f32 / CURVE_SEGMENTS as f32 * 2.0 - 1.0);
+			point_on_swing(angle, swing_radius)
+		}),
+		theme.foreground,
+	)?;
+	for side in [-1.0, 1.0] {
+		let angle = side * PENDULUM_MAX_ANGLE;
+		mesh_builder.add_simple_polyline(
+			theme.stroke_width,
+			[
+				point_on_swing(angle, swing_radius - bob_radius),
+				point_on_swing(angle, swing_radius + bob_radius),
+			],
+			theme.foreground,
+		)?;
+	}
+	for i in 1..position.subdivisions {
+		let subdivision_phase = i as f32 / position.subdivisions as f32;
+		let color = if position.phase >= subdivision_phase {
+			theme.accent
+		} else {
+			theme.foreground
+		};
+		mesh_builder.add_circle(
+			ShapeStyle::Fill,
+			point_on_swing(angle_at(subdivision_phase), swing_radius),
+			theme.stroke_width,
+			color,
+		)?;
+	}
+	let angle = angle_at(position.phase);
+	let bob_position = point_on_swing(angle, swing_radius);
+	mesh_builder.add_simple_polyline(
+		theme.stroke_width,
+		[pivot, bob_position],
+		theme.foreground,
+	)?;
+	mesh_builder.add_circle(ShapeStyle::Fill, bob_position, bob_radius, theme.accent)?;
 	Ok(())
 }
 
-fn beat_indicator_tick_points(rect: Rect, tick_index: u32, num_ticks: u32) -> [Vec2; 2] {
-	let should_shear = tick_index != 0 && tick_index != num_ticks;
-	let x = tick_index as f32 / num_ticks as f32;
+fn add_conductor(
+	mesh_builder: &mut MeshBuilder,
+	theme: &Theme,
+	rect: Rect,
+	position: BeatPosition,
+) -> anyhow::Result<()> {
+	let (center, radius) = circle_in_rect(theme, rect);
+	let pattern = conducting_pattern(position.num_beats);
+	let to_pixels = |point: Vec2| center + point * radius;
+	let baton_position_at = |phase: f32| {
+		let current = pattern[position.current_beat as usize % pattern.len()];
+		let next = pattern[(position.current_beat as usize + 1) % pattern.len()];
+		// the baton rebounds upwards after each beat before falling
+		// into the next one
+		let rebound = Vec2::new(0.0, -0.35 * (PI * phase).sin());
+		to_pixels(current.lerp(next, phase) + rebound)
+	};
+	for (beat, &point) in pattern.iter().enumerate() {
+		let point_radius = if beat == 0 {
+			theme.stroke_width * 1.5
+		} else {
+			theme.stroke_width
+		};
+		let style = if beat as u32 == position.current_beat {
+			ShapeStyle::Fill
+		} else {
+			ShapeStyle::Stroke(theme.stroke_width / 2.0)
+		};
+		mesh_builder.add_circle(style, to_pixels(point), point_radius, theme.foreground)?;
+	}
+	for i in 1..position.subdivisions {
+		let subdivision_phase = i as f32 / position.subdivisions as f32;
+		let color = if position.phase >= subdivision_phase {
+			theme.accent
+		} else {
+			theme.foreground
+		};
+		mesh_builder.add_circle(
+			ShapeStyle::Fill,
+			baton_position_at(subdivision_phase),
+			theme.stroke_width / 2.0,
+			color,
+		)?;
+	}
+	mesh_builder.add_simple_polyline(
+		theme.stroke_width,
+		(0..=CURVE_SEGMENTS / 2)
+			.map(|i| baton_position_at(position.phase * i as f32 / (CURVE_SEGMENTS / 2) as f32)),
+		with_alpha(theme.accent, 0.5),
+	)?;
+	mesh_builder.add_circle(
+		ShapeStyle::Fill,
+		baton_position_at(position.phase),
+		theme.stroke_width * 2.0,
+		theme.accent,
+	)?;
+	Ok(())
+}
+
+/// Returns the points the baton hits on each beat of a bar, relative
+/// to the center of the indicator and scaled to fit in a unit circle.
+fn conducting_pattern(num_beats: u32) -> Vec<Vec2> {
+	const DOWN: Vec2 = Vec2::new(0.0, 0.7);
+	const UP: Vec2 = Vec2::new(0.0, -0.6);
+	match num_beats {
+		1 => vec![DOWN],
+		2 => vec![DOWN, UP],
+		// compound duple meter: three beats to the left, three to
+		// the right
+		6 => vec![
+			DOWN,
+			Vec2::new(-0.3, 0.5),
+			Vec2::new(-0.6, 0.3),
+			Vec2::new(0.3, 0.5),
+			Vec2::new(0.6, 0.3),
+			UP,
+		],
+		// the downbeat, then one beat to the left (for meters in
+		// four), then the rest spread out to the right, then
+		// the upbeat
+		_ => {
+			let num_inner_beats = num_beats - 2;
+			let num_left_beats = if num_inner_beats >= 2 { 1 } else { 0 };
+			let num_right_beats = num_inner_beats - num_left_beats;
+			let mut pattern = vec![DOWN];
+			pattern.extend((0..num_left_beats).map(|_| Vec2::new(-0.6, 0.3)));
+			pattern.extend((0..num_right_beats).map(|i| {
+				let x = 0.6 * (i + 1) as f32 / num_right_beats as f32;
+				Vec2::new(x, 0.3 + 0.2 * (1.0 - x))
+			}));
+			pattern.push(UP);
+			pattern
+		}
+	}
+}
+
+fn add_dots(
+	mesh_builder: &mut MeshBuilder,
+	theme: &Theme,
+	rect: Rect,
+	position: BeatPosition,
+) -> anyhow::Result<()> {
+	let cell_width = rect.size.x / position.num_beats as f32;
+	let beat_radius = (rect.size.y.min(cell_width / position.subdivisions as f32)
+		* theme.grid_cell_size
+		/ 2.0 - theme.stroke_width)
+		.max(theme.stroke_width);
+	let subdivision_radius = beat_radius / 3.0;
+	for beat in 0..position.num_beats {
+		let y = rect.center().y * theme.grid_cell_size;
+		let cell_left = rect.left() + cell_width * beat as f32;
+		let beat_center = Vec2::new(
+			(cell_left + cell_width / (2 * position.subdivisions) as f32) * theme.grid_cell_size,
+			y,
+		);
+		let is_current_beat = beat == position.current_beat;
+		let radius = if beat == 0 {
+			beat_radius
+		} else {
+			beat_radius * 0.75
+		};
+		if is_current_beat {
+			mesh_builder.add_circle(ShapeStyle::Fill, beat_center, radius, theme.accent)?;
+		} else {
+			mesh_builder.add_circle(
+				ShapeStyle::Stroke(theme.stroke_width),
+				beat_center,
+				radius - theme.stroke_width / 2.0,
+				theme.foreground,
+			)?;
+		}
+		for subdivision in 1..position.subdivisions {
+			let x = cell_left
+				+ cell_width * (2 * subdivision + 1) as f32 / (2 * position.subdivisions) as f32;
+			let lit = is_current_beat && subdivision <= position.current_subdivision();
+			mesh_builder.add_circle(
+				ShapeStyle::Fill,
+				Vec2::new(x * theme.grid_cell_size, y),
+				subdivision_radius,
+				if lit { theme.accent } else { theme.foreground },
+			)?;
+		}
+	}
+	Ok(())
+}
+
+fn add_sweep(
+	mesh_builder: &mut MeshBuilder,
+	theme: &Theme,
+	rect: Rect,
+	position: BeatPosition,
+) -> anyhow::Result<()> {
+	let (center, radius) = circle_in_rect(theme, rect);
+	// angles start at the top of the circle and go clockwise
+	let point_at = |fraction: f32, distance: f32| {
+		let angle = fraction * TAU;
+		center + Vec2::new(angle.sin(), -angle.cos()) * distance
+	};
+	let beat_start = position.current_beat as f32 / position.num_beats as f32;
+	let progress = position.bar_progress();
+	let add_wedge = |mesh_builder: &mut MeshBuilder,
+	                 start: f32,
+	                 end: f32,
+	                 color: LinSrgba|
+	 -> anyhow::Result<()> {
+		if end <= start {
+			return Ok(());
+		}
+		let num_segments = ((end - start) * CURVE_SEGMENTS as f32).ceil().max(1.0) as usize;
+		mesh_builder.add_simple_polygon(
+			ShapeStyle::Fill,
+			std::iter::once(center).chain((0..=num_segments).map(|i| {
+				point_at(
+					start + (end - start) * i as f32 / num_segments as f32,
+					radius,
+				)
+			})),
+			color,
+		)?;
+		Ok(())
+	};
+	add_wedge(
+		mesh_builder,
+		0.0,
+		beat_start,
+		with_alpha(theme.accent, 0.25),
+	)?;
+	add_wedge(mesh_builder, beat_start, progress, theme.accent)?;
+	mesh_builder.add_circle(
+		ShapeStyle::Stroke(theme.stroke_width),
+		center,
+		radius,
+		theme.foreground,
+	)?;
+	for beat in 0..position.num_beats {
+		let fraction = beat as f32 / position.num_beats as f32;
+		let tick_length = if beat == 0 {
+			radius / 3.0
+		} else {
+			radius / 5.0
+		};
+		mesh_builder.add_simple_polyline(
+			theme.stroke_width,
+			[
+				point_at(fraction, radius),
+				point_at(fraction, radius - tick_length),
+			],
+			theme.foreground,
+		)?;
+		for subdivision in 1..position.subdivisions {
+			let fraction =
+				fraction + subdivision as f32 / (position.subdivisions * position.num_beats) as f32;
+			mesh_builder.add_simple_polyline(
+				theme.stroke_width / 2.0,
+				[
+					point_at(fraction, radius),
+					point_at(fraction, radius - radius / 10.0),
+				],
+				theme.foreground,
+			)?;
+		}
+	}
+	mesh_builder.add_simple_polyline(
+		theme.stroke_width,
+		[center, point_at(progress, radius)],
+		theme.foreground,
+	)?;
+	Ok(())
+}
+
+/// Returns the center and radius in pixels of the largest circle
+/// that fits in the given grid rect, leaving room for the stroke.
+fn circle_in_rect(theme: &Theme, rect: Rect) -> (Vec2, f32) {
+	let center = rect.center() * theme.grid_cell_size;
+	let radius = rect.size.min_element() * theme.grid_cell_size / 2.0 - theme.stroke_width;
+	(center, radius.max(theme.stroke_width))
+}
+
+fn beat_indicator_tick_points(
+	theme: &Theme,
+	rect: Rect,
+	tick_position: f32,
+	num_ticks: u32,
+) -> [Vec2; 2] {
+	let should_shear = tick_position != 0.0 && tick_position != num_ticks as f32;
+	let x = tick_position / num_ticks as f32;
 	let mut points = [
 		Vec2::new(rect.fractional_x(x), rect.top()),
 		Vec2::new(rect.fractional_x(x), rect.bottom()),
@@ -66,7 +553,7 @@ fn beat_indicator_tick_points(rect: Rect, tick_index: u32, num_ticks: u32) -> [V
 		points[0].x += rect.size.y / 2.0;
 		points[1].x -= rect.size.y / 2.0;
 	}
-	points[0] *= GRID_CELL_SIZE;
-	points[1] *= GRID_CELL_SIZE;
+	points[0] *= theme.grid_cell_size;
+	points[1] *= theme.grid_cell_size;
 	points
 }
@@ -0,0 +1,94 @@
+use glam::Vec2;
+use micro::{math::Rect, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	track_info::ValueChange,
+	transition::{TransitionKind, TransitionSettings},
+};
+
+use super::{
+	draw_transitioned_text,
+	text_cache::{TextCache, TextStyle},
+	FrameContext, Widget, PLACEHOLDER_STRING,
+};
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BpmOptions {
+	/// The number of decimal places to show. If `None`,
+	/// the BPM is shown as written in the project file.
+	pub decimal_places: Option<usize>,
+	pub transition: TransitionSettings,
+}
+
+pub struct BpmWidget {
+	options: BpmOptions,
+	text_cache: TextCache,
+}
+
+impl Widget for BpmWidget {
+	const NAME: &'static str = "bpm";
+
+	type Options = BpmOptions;
+
+	fn new(options: Self::Options) -> Self {
+		Self {
+			options,
+			text_cache: TextCache::default(),
+		}
+	}
+
+	fn size(&self) -> Vec2 {
+		Vec2::new(12.0, 4.0)
+	}
+
+	fn draw(
+		&mut self,
+		ctx: &mut Context,
+		frame: &FrameContext,
+		grid_bounds: Rect,
+	) -> anyhow::Result<()> {
+		let BpmOptions {
+			mut decimal_places,
+			transition,
+		} = self.options;
+		let mut change = frame
+			.track_info
+			.value_change(frame.timestamp, |music_state| {
+				(!music_state.bpm_hidden).then_some(music_state.bpm)
+			});
+		let progress = transition.progress(change.time_since_change) as f64;
+		// once the transition is over, the BPM is formatted as usual
+		if transition.kind == TransitionKind::CountUp && progress < 1.0 {
+			if let (Some(current), Some(Some(previous))) = (change.current, change.previous) {
+				change = ValueChange {
+					current: Some(previous + (current - previous) * progress),
+					previous: None,
+					time_since_change: change.time_since_change,
+				};
+				decimal_places = decimal_places.or(Some(0));
+			}
+		}
+		draw_transitioned_text(
+			ctx,
+			frame.theme,
+			transition,
+			&change,
+			Rect::new(
+				grid_bounds.top_left * frame.theme.grid_cell_size,
+				grid_bounds.size * frame.theme.grid_cell_size,
+			),
+			|ctx, bpm| {
+				let bpm_string = match (bpm, decimal_places) {
+					(None, _) => PLACEHOLDER_STRING.to_string(),
+					(Some(bpm), Some(decimal_places)) => format!("{:.*}", decimal_places, bpm),
+					(Some(bpm), None) => bpm.to_string(),
+				};
+				self.text_cache
+					.get(ctx, frame, TextStyle::Large, &bpm_string)
+			},
+		);
+		Ok(())
+	}
+}
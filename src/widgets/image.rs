@@ -0,0 +1,182 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as AnyhowContext;
+use glam::Vec2;
+use micro::{
+	graphics::{
+		texture::{Texture, TextureSettings},
+		DrawParams,
+	},
+	math::Rect,
+	Context,
+};
+use palette::LinSrgba;
+use serde::{Deserialize, Serialize};
+
+use crate::theme::Theme;
+
+use super::{FrameContext, Widget};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageOptions {
+	/// The path to a PNG or JPEG file, relative to the project file.
+	pub path: PathBuf,
+	#[serde(default)]
+	pub fit: ImageFit,
+	#[serde(default)]
+	pub tint: ImageTint,
+	/// The space between the image and the panel's outline in grid cells.
+	#[serde(default = "default_padding")]
+	pub padding: f32,
+	/// The name used to show or hide the image with the
+	/// `image_visibility` field of a change.
+	#[serde(default)]
+	pub id: Option<String>,
+	/// Whether the image is shown before any change sets its visibility.
+	#[serde(default = "default_visible")]
+	pub visible: bool,
+}
+
+fn default_padding() -> f32 {
+	1.0
+}
+
+fn default_visible() -> bool {
+	true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageFit {
+	/// The whole image is shown as large as possible without
+	/// changing its aspect ratio.
+	#[default]
+	Fit,
+	/// The image covers the whole panel, cropping the edges that
+	/// don't fit.
+	Fill,
+	/// The image is stretched to the size of the panel.
+	Stretch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageTint {
+	/// The image is drawn with its original colors.
+	#[default]
+	None,
+	Foreground,
+	Label,
+	Accent,
+}
+
+impl ImageTint {
+	fn color(self, theme: &Theme) -> LinSrgba {
+		match self {
+			ImageTint::None => LinSrgba::new(1.0, 1.0, 1.0, 1.0),
+			ImageTint::Foreground => theme.foreground,
+			ImageTint::Label => theme.label,
+			ImageTint::Accent => theme.accent,
+		}
+	}
+}
+
+/// Shows an image file, such as a logo or album art.
+pub struct ImageWidget {
+	options: ImageOptions,
+	texture: Option<Texture>,
+}
+
+impl Widget for ImageWidget {
+	const NAME: &'static str = "image";
+
+	type Options = ImageOptions;
+
+	fn new(options: Self::Options) -> Self {
+		Self {
+			options,
+			texture: None,
+		}
+	}
+
+	fn load(&mut self, ctx: &mut Context, project_dir: &Path) -> anyhow::Result<()> {
+		let path = project_dir.join(&self.options.path);
+		self.texture = Some(
+			Texture::from_file(ctx, &path, TextureSettings::default())
+				.with_context(|| format!("error loading image {}", path.display()))?,
+		);
+		Ok(())
+	}
+
+	fn size(&self) -> Vec2 {
+		Vec2::new(8.0, 8.0)
+	}
+
+	fn visible(&self, frame: &FrameContext) -> bool {
+		self.options
+			.id
+			.as_ref()
+			.and_then(|id| frame.music_state.image_visibility.get(id))
+			.copied()
+			.unwrap_or(self.options.visible)
+	}
+
+	fn draw(
+		&mut self,
+		ctx: &mut Context,
+		frame: &FrameContext,
+		grid_bounds: Rect,
+	) -> anyhow::Result<()> {
+		let Some(texture) = &self.texture else {
+			return Ok(());
+		};
+		let theme = frame.theme;
+		let padding = Vec2::splat(self.options.padding);
+		let region = Rect::from_top_left_and_bottom_right(
+			(grid_bounds.top_left + padding) * theme.grid_cell_size,
+			(grid_bounds.bottom_right() - padding) * theme.grid_cell_size,
+		);
+		let texture_size = texture.size().as_vec2();
+		if region.size.cmple(Vec2::ZERO).any() || texture_size.cmple(Vec2::ZERO).any() {
+			return Ok(());
+		}
+		let params = DrawParams::new().color(self.options.tint.color(theme));
+		match self.options.fit {
+			ImageFit::Fit => {
+				let scale = (region.size / texture_size).min_element();
+				let size = texture_size * scale;
+				texture.draw(
+					ctx,
+					params
+						.scaled(Vec2::splat(scale))
+						.translated(region.center() - size / 2.0),
+				);
+			}
+			ImageFit::Fill => {
+				let scale = (region.size / texture_size).max_element();
+				// only draw the part of the texture that lands inside the region
+				let visible_texture_size = region.size / scale;
+				let texture_region = Rect::new(
+					(texture_size - visible_texture_size) / 2.0,
+					visible_texture_size,
+				);
+				texture.draw_region(
+					ctx,
+					texture_region,
+					params
+						.scaled(Vec2::splat(scale))
+						.translated(region.top_left),
+				);
+			}
+			ImageFit::Stretch => {
+				texture.draw(
+					ctx,
+					params
+						.scaled(region.size / texture_size)
+						.translated(region.top_left),
+				);
+			}
+		}
+		Ok(())
+	}
+}
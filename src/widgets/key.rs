@@ -0,0 +1,68 @@
+use glam::Vec2;
+use micro::{math::Rect, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::transition::TransitionSettings;
+
+use super::{
+	draw_transitioned_text,
+	text_cache::{TextCache, TextStyle},
+	FrameContext, Widget, PLACEHOLDER_STRING,
+};
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyOptions {
+	pub transition: TransitionSettings,
+}
+
+pub struct KeyWidget {
+	options: KeyOptions,
+	text_cache: TextCache,
+}
+
+impl Widget for KeyWidget {
+	const NAME: &'static str = "key";
+
+	type Options = KeyOptions;
+
+	fn new(options: Self::Options) -> Self {
+		Self {
+			options,
+			text_cache: TextCache::default(),
+		}
+	}
+
+	fn size(&self) -> Vec2 {
+		Vec2::new(12.0, 4.0)
+	}
+
+	fn draw(
+		&mut self,
+		ctx: &mut Context,
+		frame: &FrameContext,
+		grid_bounds: Rect,
+	) -> anyhow::Result<()> {
+		draw_transitioned_text(
+			ctx,
+			frame.theme,
+			self.options.transition,
+			&frame
+				.track_info
+				.value_change(frame.timestamp, |music_state| music_state.key.clone()),
+			Rect::new(
+				grid_bounds.top_left * frame.theme.grid_cell_size,
+				grid_bounds.size * frame.theme.grid_cell_size,
+			),
+			|ctx, key| {
+				self.text_cache.get(
+					ctx,
+					frame,
+					TextStyle::Chord,
+					key.as_deref().unwrap_or(PLACEHOLDER_STRING),
+				)
+			},
+		);
+		Ok(())
+	}
+}
@@ -0,0 +1,165 @@
+use glam::Vec2;
+use micro::{
+	graphics::{
+		mesh::{MeshBuilder, ShapeStyle},
+		text::{LayoutSettings, Text},
+		DrawParams,
+	},
+	math::Rect,
+	Context,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::loudness::SILENCE_DB;
+
+use super::{
+	text_cache::{TextCache, TextStyle},
+	text_translation, FrameContext, Widget,
+};
+
+/// The width of the meter labels in grid cells.
+const LABEL_WIDTH: f32 = 3.0;
+/// The width of the numeric readouts in grid cells.
+const VALUE_WIDTH: f32 = 3.5;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoudnessOptions {
+	/// The level (in dB) at the left end of the meters.
+	pub min_db: f32,
+	/// If set, a line is drawn at this loudness on the LUFS meter.
+	pub target_lufs: Option<f32>,
+}
+
+impl Default for LoudnessOptions {
+	fn default() -> Self {
+		Self {
+			min_db: -60.0,
+			target_lufs: Some(-14.0),
+		}
+	}
+}
+
+/// Shows peak, RMS and short-term loudness meters for the current
+/// position in the song.
+pub struct LoudnessWidget {
+	options: LoudnessOptions,
+	text_cache: TextCache,
+}
+
+impl Widget for LoudnessWidget {
+	const NAME: &'static str = "loudness";
+
+	type Options = LoudnessOptions;
+
+	fn new(options: Self::Options) -> Self {
+		Self {
+			options,
+			text_cache: TextCache::default(),
+		}
+	}
+
+	fn size(&self) -> Vec2 {
+		Vec2::new(16.0, 6.0)
+	}
+
+	fn draw(
+		&mut self,
+		ctx: &mut Context,
+		frame: &FrameContext,
+		grid_bounds: Rect,
+	) -> anyhow::Result<()> {
+		let theme = frame.theme;
+		let levels = frame.loudness.at(frame.timestamp);
+		let region = Rect::from_top_left_and_bottom_right(
+			grid_bounds.top_left + Vec2::new(1.0, 1.0),
+			grid_bounds.bottom_right() - Vec2::new(1.0, 0.5),
+		);
+		let row_height = region.size.y / 3.0;
+		let min_db = self.options.min_db.min(-1.0);
+		let fraction_at = |db: f32| (1.0 - db / min_db).clamp(0.0, 1.0);
+		let mut mesh_builder = MeshBuilder::new();
+		for (i, (label, level, target)) in [
+			("PEAK", levels.peak, None),
+			("RMS", levels.rms, None),
+			("LUFS", levels.short_term_lufs, self.options.target_lufs),
+		]
+		.into_iter()
+		.enumerate()
+		{
+			let row = Rect::new(
+				region.top_left + Vec2::new(0.0, row_height * i as f32),
+				Vec2::new(region.size.x, row_height),
+			);
+			let label_text = self.text_cache.get(ctx, frame, TextStyle::Label, label);
+			label_text.draw(
+				ctx,
+				DrawParams::new()
+					.translated(text_translation(
+						&label_text,
+						Vec2::new(row.left(), row.center().y) * theme.grid_cell_size,
+						Vec2::new(0.0, 0.5),
+					))
+					.color(theme.foreground),
+			);
+			// the readout changes every frame, so there's no point caching it
+			let value_string = if level <= SILENCE_DB {
+				"-inf".to_string()
+			} else {
+				format!("{:.1}", level)
+			};
+			let value_text = Text::new(
+				ctx,
+				&frame.fonts.small,
+				&value_string,
+				LayoutSettings::default(),
+			);
+			value_text.draw(
+				ctx,
+				DrawParams::new()
+					.translated(text_translation(
+						&value_text,
+						Vec2::new(row.right(), row.center().y) * theme.grid_cell_size,
+						Vec2::new(1.0, 0.5),
+					))
+					.color(theme.foreground),
+			);
+			let bar = Rect::from_top_left_and_bottom_right(
+				Vec2::new(row.left() + LABEL_WIDTH, row.top() + row_height / 4.0)
+					* theme.grid_cell_size,
+				Vec2::new(row.right() - VALUE_WIDTH, row.bottom() - row_height / 4.0)
+					* theme.grid_cell_size,
+			);
+			let fill_width = bar.size.x * fraction_at(level);
+			if fill_width > 0.0 {
+				mesh_builder.add_rectangle(
+					ShapeStyle::Fill,
+					Rect::new(bar.top_left, Vec2::new(fill_width, bar.size.y)),
+					if level >= 0.0 {
+						theme.accent
+					} else {
+						theme.foreground
+					},
+				)?;
+			}
+			mesh_builder.add_rectangle(
+				ShapeStyle::Stroke(theme.stroke_width / 2.0),
+				bar,
+				theme.foreground,
+			)?;
+			if let Some(target) = target {
+				let x = bar.fractional_x(fraction_at(target));
+				mesh_builder.add_simple_polyline(
+					theme.stroke_width / 2.0,
+					[
+						Vec2::new(x, bar.top() - theme.stroke_width),
+						Vec2::new(x, bar.bottom() + theme.stroke_width),
+					],
+					theme.accent,
+				)?;
+			}
+		}
+		mesh_builder.build(ctx).draw(ctx, DrawParams::new());
+		Ok(())
+	}
+}
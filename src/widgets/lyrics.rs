@@ -0,0 +1,119 @@
+use glam::Vec2;
+use micro::{graphics::DrawParams, math::Rect, Context};
+use serde::{Deserialize, Serialize};
+
+use super::{
+	text_cache::{TextCache, TextStyle},
+	text_translation, with_alpha, FrameContext, Widget,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LyricsOptions {
+	/// Whether words are highlighted as they're sung. Only has an effect
+	/// for lyrics imported from enhanced LRC files.
+	pub karaoke: bool,
+	pub show_next_line: bool,
+}
+
+impl Default for LyricsOptions {
+	fn default() -> Self {
+		Self {
+			karaoke: true,
+			show_next_line: true,
+		}
+	}
+}
+
+/// Shows the current line of the project's lyrics and the line after it.
+pub struct LyricsWidget {
+	options: LyricsOptions,
+	text_cache: TextCache,
+}
+
+impl Widget for LyricsWidget {
+	const NAME: &'static str = "lyrics";
+
+	type Options = LyricsOptions;
+
+	fn new(options: Self::Options) -> Self {
+		Self {
+			options,
+			text_cache: TextCache::default(),
+		}
+	}
+
+	fn size(&self) -> Vec2 {
+		Vec2::new(32.0, 6.0)
+	}
+
+	fn draw(
+		&mut self,
+		ctx: &mut Context,
+		frame: &FrameContext,
+		grid_bounds: Rect,
+	) -> anyhow::Result<()> {
+		let Some(lyrics) = frame.lyrics else {
+			return Ok(());
+		};
+		let theme = frame.theme;
+		let region = Rect::from_top_left_and_bottom_right(
+			(grid_bounds.top_left + Vec2::new(1.0, 1.0)) * theme.grid_cell_size,
+			(grid_bounds.bottom_right() - Vec2::new(1.0, 0.5)) * theme.grid_cell_size,
+		);
+		let current_line_index = lyrics.line_index(frame.timestamp);
+		if let Some(line) = current_line_index.map(|index| &lyrics.lines[index]) {
+			if !line.text.is_empty() {
+				let text = self
+					.text_cache
+					.get(ctx, frame, TextStyle::Medium, &line.text);
+				let translation = text_translation(
+					&text,
+					Vec2::new(region.center().x, region.fractional_y(0.35)),
+					Vec2::splat(0.5),
+				);
+				text.draw(
+					ctx,
+					DrawParams::new()
+						.translated(translation)
+						.color(theme.foreground),
+				);
+				// the sung part of the line is laid out from the same
+				// origin as the whole line, so drawing it with the same
+				// translation lines it up with the text underneath
+				let sung_text = line.sung_text(frame.timestamp);
+				if self.options.karaoke && !sung_text.is_empty() {
+					self.text_cache
+						.get(ctx, frame, TextStyle::Medium, sung_text)
+						.draw(
+							ctx,
+							DrawParams::new()
+								.translated(translation)
+								.color(theme.accent),
+						);
+				}
+			}
+		}
+		if self.options.show_next_line {
+			let next_line_index = current_line_index.map_or(0, |index| index + 1);
+			if let Some(line) = lyrics.lines.get(next_line_index) {
+				if !line.text.is_empty() {
+					let text = self
+						.text_cache
+						.get(ctx, frame, TextStyle::Label, &line.text);
+					text.draw(
+						ctx,
+						DrawParams::new()
+							.translated(text_translation(
+								&text,
+								Vec2::new(region.center().x, region.fractional_y(0.8)),
+								Vec2::splat(0.5),
+							))
+							.color(with_alpha(theme.foreground, 0.6)),
+					);
+				}
+			}
+		}
+		Ok(())
+	}
+}
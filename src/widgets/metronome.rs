@@ -0,0 +1,112 @@
+use glam::Vec2;
+use micro::{graphics::DrawParams, math::Rect, Context};
+use serde::{Deserialize, Serialize};
+
+use super::{
+	beat_indicator::{draw_beat_indicator, draw_beat_indicator_placeholder, BeatIndicatorSettings},
+	text_cache::{TextCache, TextStyle},
+	text_translation, FrameContext, Widget, PLACEHOLDER_STRING,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetronomeOptions {
+	pub show_time_signature: bool,
+	pub indicator: BeatIndicatorSettings,
+}
+
+impl Default for MetronomeOptions {
+	fn default() -> Self {
+		Self {
+			show_time_signature: true,
+			indicator: BeatIndicatorSettings::default(),
+		}
+	}
+}
+
+pub struct MetronomeWidget {
+	options: MetronomeOptions,
+	text_cache: TextCache,
+}
+
+impl Widget for MetronomeWidget {
+	const NAME: &'static str = "metronome";
+
+	type Options = MetronomeOptions;
+
+	fn new(options: Self::Options) -> Self {
+		Self {
+			options,
+			text_cache: TextCache::default(),
+		}
+	}
+
+	fn size(&self) -> Vec2 {
+		Vec2::new(12.0, 5.0)
+	}
+
+	fn draw(
+		&mut self,
+		ctx: &mut Context,
+		frame: &FrameContext,
+		grid_bounds: Rect,
+	) -> anyhow::Result<()> {
+		let theme = frame.theme;
+		let indicator_style = self.options.indicator.style;
+		// horizontal indicators go underneath the time signature, and
+		// the others go to the right of it
+		let (text_region, indicator_region) = match (
+			indicator_style.is_horizontal(),
+			self.options.show_time_signature,
+		) {
+			(true, _) => (
+				grid_bounds.resized_y(grid_bounds.size.y - 1.0, 0.0),
+				grid_bounds.resized_y(1.0, 1.0),
+			),
+			(false, true) => (
+				grid_bounds.resized_x(grid_bounds.size.x - grid_bounds.size.y, 0.0),
+				grid_bounds.resized_x(grid_bounds.size.y, 1.0),
+			),
+			(false, false) => (grid_bounds, grid_bounds.resized_x(grid_bounds.size.y, 0.5)),
+		};
+		if self.options.show_time_signature {
+			let text = self.text_cache.get(
+				ctx,
+				frame,
+				TextStyle::Large,
+				&frame
+					.music_state
+					.time_signature
+					.map(|time_signature| time_signature.to_string())
+					.unwrap_or_else(|| PLACEHOLDER_STRING.to_string()),
+			);
+			text.draw(
+				ctx,
+				DrawParams::new()
+					.translated(text_translation(
+						&text,
+						text_region.center() * theme.grid_cell_size,
+						Vec2::splat(0.5),
+					))
+					.color(theme.foreground),
+			);
+		}
+		if let (Some(time_signature), Some(current_beat)) = (
+			frame.music_state.time_signature,
+			frame.beat_info.beat_in_bar,
+		) {
+			draw_beat_indicator(
+				ctx,
+				theme,
+				self.options.indicator,
+				indicator_region,
+				time_signature,
+				current_beat,
+				frame.beat_info.phase,
+			)?;
+		} else {
+			draw_beat_indicator_placeholder(ctx, theme, indicator_style, indicator_region)?;
+		}
+		Ok(())
+	}
+}
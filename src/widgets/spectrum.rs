@@ -0,0 +1,188 @@
+use std::time::Duration;
+
+use glam::Vec2;
+use micro::{
+	graphics::{
+		mesh::{MeshBuilder, ShapeStyle},
+		DrawParams,
+	},
+	math::Rect,
+	Context,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{spectrum::SpectrumAnalyzer, EXPORT_FPS};
+
+use super::{FrameContext, Widget};
+
+const FFT_SIZE: usize = 4096;
+/// The most past spectrums that smoothing will look at.
+const MAX_SMOOTHING_STEPS: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpectrumOptions {
+	/// The number of bars to show.
+	pub bands: usize,
+	pub scale: FrequencyScale,
+	/// The lowest frequency shown, in Hz.
+	pub min_frequency: f32,
+	/// The highest frequency shown, in Hz.
+	pub max_frequency: f32,
+	/// The volume (in decibels) that corresponds to an empty bar.
+	pub min_db: f32,
+	/// How slowly bars fall after a peak (from 0 to 1). `0.0`
+	/// shows the spectrum of each frame as-is.
+	pub smoothing: f32,
+}
+
+impl Default for SpectrumOptions {
+	fn default() -> Self {
+		Self {
+			bands: 32,
+			scale: FrequencyScale::Logarithmic,
+			min_frequency: 20.0,
+			max_frequency: 20000.0,
+			min_db: -60.0,
+			smoothing: 0.7,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FrequencyScale {
+	/// Each band covers the same number of Hz.
+	Linear,
+	/// Each band covers the same number of octaves.
+	#[default]
+	Logarithmic,
+}
+
+/// Shows the volume of different frequency bands as bars.
+pub struct SpectrumWidget {
+	options: SpectrumOptions,
+	analyzer: SpectrumAnalyzer,
+}
+
+impl SpectrumWidget {
+	/// Returns the height (from 0 to 1) of each bar at the given time.
+	///
+	/// Smoothing looks at the spectrums from previous frames rather
+	/// than remembering what was drawn, so the result only depends on
+	/// the timestamp.
+	fn band_levels(&mut self, frame: &FrameContext) -> Vec<f32> {
+		let smoothing = self.options.smoothing.clamp(0.0, 0.99);
+		let num_steps = if smoothing > 0.0 {
+			((0.01f32.ln() / smoothing.ln()).ceil() as usize).clamp(1, MAX_SMOOTHING_STEPS)
+		} else {
+			1
+		};
+		let step = Duration::from_secs_f64(1.0 / EXPORT_FPS);
+		let mut levels = vec![0.0f32; self.options.bands.max(1)];
+		for i in 0..num_steps {
+			let Some(timestamp) = frame.timestamp.checked_sub(step * i as u32) else {
+				break;
+			};
+			let weight = smoothing.powi(i as i32);
+			let magnitudes = self.analyzer.magnitudes(frame.sound_data, timestamp);
+			for (band, level) in levels.iter_mut().enumerate() {
+				let magnitude =
+					self.band_magnitude(&magnitudes, frame.sound_data.sample_rate, band);
+				*level = level.max(self.magnitude_to_level(magnitude) * weight);
+			}
+		}
+		levels
+	}
+
+	fn band_magnitude(&self, magnitudes: &[f32], sample_rate: u32, band: usize) -> f32 {
+		let num_bands = self.options.bands.max(1);
+		let min_frequency = self.options.min_frequency.max(1.0);
+		let max_frequency = self.options.max_frequency.max(min_frequency);
+		let frequency_at = |fraction: f32| match self.options.scale {
+			FrequencyScale::Linear => min_frequency + (max_frequency - min_frequency) * fraction,
+			FrequencyScale::Logarithmic => {
+				min_frequency * (max_frequency / min_frequency).powf(fraction)
+			}
+		};
+		let bin_at =
+			|frequency: f32| frequency * self.analyzer.fft_size() as f32 / sample_rate as f32;
+		let start_bin = bin_at(frequency_at(band as f32 / num_bands as f32));
+		let end_bin = bin_at(frequency_at((band + 1) as f32 / num_bands as f32));
+		let last_bin = magnitudes.len() - 1;
+		let first = (start_bin.ceil() as usize).min(last_bin);
+		let last = (end_bin.floor() as usize).min(last_bin);
+		if first <= last {
+			magnitudes[first..=last].iter().copied().fold(0.0, f32::max)
+		} else {
+			// low bands can be narrower than a single bin, so
+			// interpolate between the nearest bins instead
+			let center_bin = ((start_bin + end_bin) / 2.0).min(last_bin as f32);
+			let lower = center_bin.floor() as usize;
+			let upper = (lower + 1).min(last_bin);
+			let fraction = center_bin.fract();
+			magnitudes[lower] * (1.0 - fraction) + magnitudes[upper] * fraction
+		}
+	}
+
+	fn magnitude_to_level(&self, magnitude: f32) -> f32 {
+		if magnitude <= 0.0 || self.options.min_db >= 0.0 {
+			return 0.0;
+		}
+		let db = 20.0 * magnitude.log10();
+		(1.0 - db / self.options.min_db).clamp(0.0, 1.0)
+	}
+}
+
+impl Widget for SpectrumWidget {
+	const NAME: &'static str = "spectrum";
+
+	type Options = SpectrumOptions;
+
+	fn new(options: Self::Options) -> Self {
+		Self {
+			options,
+			analyzer: SpectrumAnalyzer::new(FFT_SIZE),
+		}
+	}
+
+	fn size(&self) -> Vec2 {
+		Vec2::new(16.0, 6.0)
+	}
+
+	fn draw(
+		&mut self,
+		ctx: &mut Context,
+		frame: &FrameContext,
+		grid_bounds: Rect,
+	) -> anyhow::Result<()> {
+		let theme = frame.theme;
+		let levels = self.band_levels(frame);
+		let region = Rect::from_top_left_and_bottom_right(
+			(grid_bounds.top_left + Vec2::new(1.0, 1.0)) * theme.grid_cell_size,
+			(grid_bounds.bottom_right() - Vec2::new(1.0, 0.5)) * theme.grid_cell_size,
+		);
+		let band_width = region.size.x / levels.len() as f32;
+		let gap = (band_width / 4.0).min(theme.stroke_width);
+		let mut mesh_builder = MeshBuilder::new();
+		for (i, level) in levels.iter().enumerate() {
+			let height = region.size.y * level;
+			if height <= 0.0 {
+				continue;
+			}
+			mesh_builder.add_rectangle(
+				ShapeStyle::Fill,
+				Rect::new(
+					Vec2::new(
+						region.left() + band_width * i as f32 + gap / 2.0,
+						region.bottom() - height,
+					),
+					Vec2::new(band_width - gap, height),
+				),
+				theme.foreground,
+			)?;
+		}
+		mesh_builder.build(ctx).draw(ctx, DrawParams::new());
+		Ok(())
+	}
+}
@@ -0,0 +1,91 @@
+use std::{
+	collections::HashMap,
+	rc::Rc,
+	sync::atomic::{AtomicBool, Ordering},
+	time::Duration,
+};
+
+use micro::{
+	graphics::text::{LayoutSettings, Text},
+	Context,
+};
+
+use super::{chord_text, FrameContext};
+
+/// Whether [`TextCache`]s keep texts between frames. Only turned off
+/// to measure the difference caching makes.
+static TEXT_CACHE_ENABLED: AtomicBool = AtomicBool::new(true);
+
+pub fn set_text_cache_enabled(enabled: bool) {
+	TEXT_CACHE_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// The fonts and layout used to build a [`Text`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextStyle {
+	/// A panel title.
+	Label,
+	/// A single line in the medium font.
+	Medium,
+	/// A single line in the large font.
+	Large,
+	/// A chord or key name, with the chord quality and accidentals
+	/// in smaller fonts.
+	Chord,
+}
+
+/// Keeps laid out texts around so widgets don't have to rebuild them
+/// every frame.
+///
+/// Texts that weren't used in the previous frame are dropped, so texts
+/// that change often (like the sung part of a lyric line) don't pile up.
+#[derive(Default)]
+pub struct TextCache {
+	texts: HashMap<(TextStyle, String), CachedText>,
+	/// The timestamp of the frame being drawn.
+	frame_timestamp: Option<Duration>,
+}
+
+struct CachedText {
+	text: Rc<Text>,
+	used_this_frame: bool,
+}
+
+impl TextCache {
+	pub fn get(
+		&mut self,
+		ctx: &mut Context,
+		frame: &FrameContext,
+		style: TextStyle,
+		string: &str,
+	) -> Rc<Text> {
+		if !TEXT_CACHE_ENABLED.load(Ordering::Relaxed) {
+			self.texts.clear();
+		}
+		if self.frame_timestamp != Some(frame.timestamp) {
+			self.texts
+				.retain(|_, cached| std::mem::take(&mut cached.used_this_frame));
+			self.frame_timestamp = Some(frame.timestamp);
+		}
+		let cached = self
+			.texts
+			.entry((style, string.to_string()))
+			.or_insert_with(|| CachedText {
+				text: Rc::new(match style {
+					TextStyle::Label => {
+						Text::new(ctx, &frame.fonts.small, string, LayoutSettings::default())
+					}
+					TextStyle::Medium => {
+						Text::new(ctx, &frame.fonts.medium, string, LayoutSettings::default())
+					}
+					TextStyle::Large => {
+						Text::new(ctx, &frame.fonts.large, string, LayoutSettings::default())
+					}
+					TextStyle::Chord => chord_text(ctx, string, frame.fonts),
+				}),
+				used_this_frame: false,
+			});
+		cached.used_this_frame = true;
+		cached.text.clone()
+	}
+}
@@ -0,0 +1,203 @@
+use glam::Vec2;
+use kira::sound::static_sound::StaticSoundData;
+use micro::{
+	graphics::{
+		mesh::{MeshBuilder, ShapeStyle},
+		DrawParams,
+	},
+	math::Rect,
+	Context,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{with_alpha, FrameContext, Widget};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimelineOptions {
+	/// The number of columns the waveform is split into.
+	pub resolution: usize,
+	pub show_key_changes: bool,
+	pub show_tempo_changes: bool,
+	pub show_time_signature_changes: bool,
+	pub show_chord_changes: bool,
+	pub show_section_changes: bool,
+}
+
+impl Default for TimelineOptions {
+	fn default() -> Self {
+		Self {
+			resolution: 400,
+			show_key_changes: true,
+			show_tempo_changes: true,
+			show_time_signature_changes: true,
+			show_chord_changes: false,
+			show_section_changes: true,
+		}
+	}
+}
+
+/// Shows the waveform of the whole song with markers where the
+/// music changes and a playhead at the current position.
+pub struct TimelineWidget {
+	options: TimelineOptions,
+	/// The lowest and highest sample in each column of the waveform,
+	/// scaled so the loudest sample in the song is `1.0`. Calculated
+	/// the first time the widget is drawn.
+	waveform: Option<Vec<(f32, f32)>>,
+}
+
+impl Widget for TimelineWidget {
+	const NAME: &'static str = "timeline";
+
+	type Options = TimelineOptions;
+
+	fn new(options: Self::Options) -> Self {
+		Self {
+			options,
+			waveform: None,
+		}
+	}
+
+	fn size(&self) -> Vec2 {
+		Vec2::new(24.0, 4.0)
+	}
+
+	fn draw(
+		&mut self,
+		ctx: &mut Context,
+		frame: &FrameContext,
+		grid_bounds: Rect,
+	) -> anyhow::Result<()> {
+		let theme = frame.theme;
+		let resolution = self.options.resolution.max(1);
+		let waveform = self
+			.waveform
+			.get_or_insert_with(|| downsample_waveform(frame.sound_data, resolution));
+		let region = Rect::from_top_left_and_bottom_right(
+			(grid_bounds.top_left + Vec2::new(1.0, 1.0)) * theme.grid_cell_size,
+			(grid_bounds.bottom_right() - Vec2::new(1.0, 0.5)) * theme.grid_cell_size,
+		);
+		let song_duration = frame.sound_data.duration().as_secs_f64();
+		let x_at = |seconds: f64| {
+			let fraction = if song_duration > 0.0 {
+				(seconds / song_duration).clamp(0.0, 1.0) as f32
+			} else {
+				0.0
+			};
+			region.fractional_x(fraction)
+		};
+		let playhead_x = x_at(frame.timestamp.as_secs_f64());
+		let mut mesh_builder = MeshBuilder::new();
+		let column_width = region.size.x / waveform.len() as f32;
+		for (i, &(min, max)) in waveform.iter().enumerate() {
+			let left = region.left() + column_width * i as f32;
+			let color = if left < playhead_x {
+				theme.foreground
+			} else {
+				with_alpha(theme.foreground, 0.35)
+			};
+			let top = region.fractional_y(0.5 - max / 2.0);
+			let bottom = region.fractional_y(0.5 - min / 2.0);
+			mesh_builder.add_rectangle(
+				ShapeStyle::Fill,
+				Rect::new(
+					Vec2::new(left, top),
+					// keep silent parts visible as a thin line
+					Vec2::new(column_width, (bottom - top).max(theme.stroke_width / 4.0)),
+				),
+				color,
+			)?;
+		}
+		let marker_size = theme.stroke_width * 1.5;
+		for pair in frame.track_info.music_states.windows(2) {
+			let (previous, current) = (&pair[0].music_state, &pair[1].music_state);
+			let x = x_at(pair[1].timestamp.as_secs_f64());
+			if self.options.show_chord_changes && current.chord != previous.chord {
+				mesh_builder.add_simple_polyline(
+					theme.stroke_width / 4.0,
+					[
+						Vec2::new(x, region.fractional_y(0.75)),
+						Vec2::new(x, region.bottom()),
+					],
+					with_alpha(theme.foreground, 0.5),
+				)?;
+			}
+			if self.options.show_section_changes && current.section != previous.section {
+				mesh_builder.add_simple_polyline(
+					theme.stroke_width / 2.0,
+					[Vec2::new(x, region.top()), Vec2::new(x, region.bottom())],
+					with_alpha(theme.foreground, 0.5),
+				)?;
+			}
+			if self.options.show_key_changes && current.key != previous.key {
+				mesh_builder.add_simple_polyline(
+					theme.stroke_width / 2.0,
+					[Vec2::new(x, region.top()), Vec2::new(x, region.bottom())],
+					theme.accent,
+				)?;
+			}
+			if self.options.show_tempo_changes && current.bpm != previous.bpm {
+				mesh_builder.add_simple_polygon(
+					ShapeStyle::Fill,
+					[
+						Vec2::new(x - marker_size, region.top()),
+						Vec2::new(x + marker_size, region.top()),
+						Vec2::new(x, region.top() + marker_size),
+					],
+					theme.accent,
+				)?;
+			}
+			if self.options.show_time_signature_changes
+				&& current.time_signature != previous.time_signature
+			{
+				mesh_builder.add_simple_polygon(
+					ShapeStyle::Fill,
+					[
+						Vec2::new(x - marker_size, region.bottom()),
+						Vec2::new(x, region.bottom() - marker_size),
+						Vec2::new(x + marker_size, region.bottom()),
+					],
+					theme.foreground,
+				)?;
+			}
+		}
+		mesh_builder.add_simple_polyline(
+			theme.stroke_width,
+			[
+				Vec2::new(playhead_x, region.top() - theme.stroke_width),
+				Vec2::new(playhead_x, region.bottom() + theme.stroke_width),
+			],
+			theme.foreground,
+		)?;
+		mesh_builder.build(ctx).draw(ctx, DrawParams::new());
+		Ok(())
+	}
+}
+
+fn downsample_waveform(sound_data: &StaticSoundData, resolution: usize) -> Vec<(f32, f32)> {
+	let frames = &sound_data.frames;
+	let mut columns = (0..resolution)
+		.map(|i| {
+			let start = i * frames.len() / resolution;
+			let end = ((i + 1) * frames.len() / resolution).max(start + 1);
+			frames[start.min(frames.len())..end.min(frames.len())]
+				.iter()
+				.map(|frame| (frame.left + frame.right) / 2.0)
+				.fold((0.0f32, 0.0f32), |(min, max), sample| {
+					(min.min(sample), max.max(sample))
+				})
+		})
+		.collect::<Vec<_>>();
+	let peak = columns
+		.iter()
+		.map(|(min, max)| min.abs().max(max.abs()))
+		.fold(0.0, f32::max);
+	if peak > 0.0 {
+		for (min, max) in &mut columns {
+			*min /= peak;
+			*max /= peak;
+		}
+	}
+	columns
+}